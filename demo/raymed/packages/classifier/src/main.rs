@@ -3,7 +3,8 @@ use std::path::PathBuf;
 use dataset::{merge_and_shuffle_datasets, pick_test_data, read_dataset_csv, Class};
 use rand::{rngs::StdRng, SeedableRng};
 use ray_ml::{
-    nd::Array1, ActivationFunction, DataPoint, NeuralNetwork, QuantizedNeuralNetwork, Trainer,
+    nd::Array1, ActivationFunction, CategoricalCrossEntropy, DataPoint, EarlyStopping, Metric,
+    NeuralNetwork, QuantizationConfig, QuantizedNeuralNetwork, Trainer,
 };
 use ray_shared::result::Result;
 
@@ -13,8 +14,21 @@ fn load_dataset(rng: &mut StdRng) -> Vec<(Vec<i8>, Class)> {
     let normal = read_dataset_csv("./dataset/normal.csv");
     let faint = read_dataset_csv("./dataset/faint.csv");
     let seizure = read_dataset_csv("./dataset/seizure.csv");
-    let dataset = merge_and_shuffle_datasets(vec![normal, faint, seizure], rng);
-    return dataset;
+    merge_and_shuffle_datasets(vec![normal, faint, seizure], rng)
+}
+
+fn to_data_points(samples: &[(Vec<i8>, Class)]) -> Vec<DataPoint> {
+    samples
+        .iter()
+        .map(|input| {
+            let inputs = Array1::from_iter(input.0.iter().map(|&pixel| pixel as f32 / 127.0));
+
+            let mut targets = Array1::zeros(3);
+            targets[input.1.to_usize()] = 1.0;
+
+            DataPoint { inputs, targets }
+        })
+        .collect()
 }
 
 fn main() -> Result<()> {
@@ -41,44 +55,63 @@ fn train() -> Result<()> {
     let mut training_data = load_dataset(&mut rng);
     let dataset_size = training_data.len();
     let _test_data = pick_test_data(&mut training_data, dataset_size / 10, &mut rng);
+    let validation_data = pick_test_data(&mut training_data, dataset_size / 10, &mut rng);
 
-    let training_data: Vec<DataPoint> = training_data
-        .iter()
-        .map(|input| {
-            let inputs = Array1::from_iter(input.0.iter().map(|&pixel| pixel as f32 / 127.0));
-
-            let mut targets = Array1::zeros(3);
-            targets[input.1.to_usize() as usize] = 1.0;
-
-            DataPoint { inputs, targets }
-        })
-        .collect();
+    let training_data = to_data_points(&training_data);
+    let validation_data = to_data_points(&validation_data);
 
     let layer_sizes = [60, 30, 15, 3];
     let activations = [
         ActivationFunction::Sigmoid,
         ActivationFunction::Sigmoid,
-        ActivationFunction::Sigmoid,
+        ActivationFunction::Softmax,
     ];
 
     let mut network = NeuralNetwork::new(&layer_sizes, &activations, &mut rng);
 
-    let mut trainer = Trainer::new(&mut network, 0.2f32, 0.7f32);
+    let mut trainer = Trainer::with_loss(
+        &mut network,
+        0.2f32,
+        0.7f32,
+        CategoricalCrossEntropy::default(),
+    );
+    trainer.validation = Some(&validation_data);
+    trainer.validation_interval = 10;
+    trainer.early_stopping = Some(EarlyStopping::new(Metric::Loss, 200));
+
+    // Pick up an interrupted run instead of starting over.
+    let checkpoint_path = PathBuf::from("training_checkpoint.bin");
+    if checkpoint_path.exists() {
+        trainer.resume_from_file(&checkpoint_path)?;
+        println!("Resuming from epoch {}", trainer.epoch);
+    }
+    trainer.checkpoint_path = Some(checkpoint_path.clone());
+    trainer.checkpoint_interval = 100;
 
-    trainer.train(&training_data, 10_000);
+    let epochs = 10_000usize;
+    trainer.train(&training_data, epochs.saturating_sub(trainer.epoch))?;
+    std::fs::remove_file(&checkpoint_path)?;
 
     let fp_model_path = PathBuf::from("model_floating_point.bin");
     network.save_to_file(&fp_model_path)?;
     println!("Floating-point model saved to {:?}", fp_model_path);
 
-    let quantized_network = network.quantize();
+    let quantized_network = quantize(&network)?;
 
     let quant_model_path = PathBuf::from("model_quantized.bin");
     quantized_network.save_to_file(&quant_model_path)?;
     println!("Quantized model saved to {:?}", quant_model_path);
 
-    let mut fine_trainer = Trainer::new(&mut network, 0.2f32, 0.8f32);
-    fine_trainer.fine_tune(&training_data, 5_000);
+    let mut fine_trainer = Trainer::with_loss(
+        &mut network,
+        0.2f32,
+        0.8f32,
+        CategoricalCrossEntropy::default(),
+    );
+    fine_trainer.validation = Some(&validation_data);
+    fine_trainer.validation_interval = 10;
+    fine_trainer.early_stopping = Some(EarlyStopping::new(Metric::Accuracy, 200));
+    fine_trainer.fine_tune(&training_data, 5_000)?;
 
     let fine_tuned_model_path = PathBuf::from("model_fine_tuned.bin");
     network.save_to_file(&fine_tuned_model_path)?;
@@ -87,7 +120,7 @@ fn train() -> Result<()> {
         fine_tuned_model_path
     );
 
-    let requantized_network = network.quantize();
+    let requantized_network = quantize(&network)?;
 
     let requant_model_path = PathBuf::from("model_requantized.bin");
     requantized_network.save_to_file(&requant_model_path)?;
@@ -95,6 +128,14 @@ fn train() -> Result<()> {
     Ok(())
 }
 
+/// Quantizes with the default configuration, printing what it had to adjust.
+fn quantize(network: &NeuralNetwork) -> Result<QuantizedNeuralNetwork> {
+    for warning in network.quantization_warnings(&QuantizationConfig::default()) {
+        println!("{}", warning);
+    }
+    network.quantize()
+}
+
 fn eval() -> Result<()> {
     let seed: u64 = 100;
     let mut rng = StdRng::seed_from_u64(seed);
@@ -103,36 +144,16 @@ fn eval() -> Result<()> {
     let dataset_size = training_data.len();
     let test_data = pick_test_data(&mut training_data, dataset_size / 10, &mut rng);
 
-    let test_data: Vec<DataPoint> = test_data
-        .iter()
-        .map(|input| {
-            let inputs = Array1::from_iter(input.0.iter().map(|&pixel| pixel as f32 / 127.0));
-
-            let mut targets = Array1::zeros(3);
-            targets[input.1.to_usize() as usize] = 1.0;
-
-            DataPoint { inputs, targets }
-        })
-        .collect();
+    let test_data = to_data_points(&test_data);
 
     let requant_model_path = PathBuf::from("model_requantized.bin");
     let requantized_network = QuantizedNeuralNetwork::load_from_file(&requant_model_path)?;
 
     let mut correct = 0;
     for point in &test_data {
-        let quant_inputs = point
-            .inputs
-            .mapv(|x| (x * 127.0).round().clamp(-128.0, 127.0) as i8);
+        let quant_inputs = requantized_network.quantize_input(&point.inputs);
 
-        let output = requantized_network.feedforward(&quant_inputs);
-        let output_float = output.mapv(|x| x as f32 / 127.0);
-
-        let prediction = output_float
-            .iter()
-            .enumerate()
-            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-            .map(|(idx, _)| idx)
-            .unwrap();
+        let prediction = requantized_network.predict(&quant_inputs);
 
         let target = point
             .targets
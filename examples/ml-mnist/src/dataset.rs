@@ -5,6 +5,7 @@ pub struct LabeledImage<const W: usize, const H: usize> {
     pub label: u8,
 }
 
+#[allow(dead_code)]
 impl<const W: usize, const H: usize> LabeledImage<W, H> {
     fn new(label: u8, data: [[u8; W]; H]) -> LabeledImage<W, H> {
         LabeledImage { label, data }
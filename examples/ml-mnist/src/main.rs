@@ -5,7 +5,8 @@ use std::path::PathBuf;
 use dataset::read_images_from_csv;
 use rand::{rngs::StdRng, SeedableRng};
 use ray_ml::{
-    nd::Array1, ActivationFunction, DataPoint, NeuralNetwork, QuantizedNeuralNetwork, Trainer,
+    nd::Array1, ActivationFunction, CategoricalCrossEntropy, CsvLogger, DataPoint, NeuralNetwork,
+    QuantizationConfig, QuantizedNeuralNetwork, Trainer,
 };
 use ray_shared::result::Result;
 
@@ -33,7 +34,7 @@ fn train() -> Result<()> {
     let activations = [
         ActivationFunction::Sigmoid,
         ActivationFunction::Sigmoid,
-        ActivationFunction::Sigmoid,
+        ActivationFunction::Softmax,
     ];
 
     let mut network = NeuralNetwork::new(&layer_sizes, &activations, &mut rng);
@@ -59,22 +60,41 @@ fn train() -> Result<()> {
         })
         .collect();
 
-    let mut trainer = Trainer::new(&mut network, 0.2f32, 0.7f32);
+    let mut trainer = Trainer::with_loss(
+        &mut network,
+        0.2f32,
+        0.7f32,
+        CategoricalCrossEntropy::default(),
+    );
+    trainer.batch_size = Some(64);
+    trainer.seed = seed;
+    trainer
+        .callbacks
+        .push(Box::new(CsvLogger::create(&PathBuf::from(
+            "training_log.csv",
+        ))?));
 
-    trainer.train(&training_data, 10_000);
+    trainer.train(&training_data, 10_000)?;
 
     let fp_model_path = PathBuf::from("model_floating_point.bin");
     network.save_to_file(&fp_model_path)?;
     println!("Floating-point model saved to {:?}", fp_model_path);
 
-    let quantized_network = network.quantize();
+    let quantized_network = quantize(&network)?;
 
     let quant_model_path = PathBuf::from("model_quantized.bin");
     quantized_network.save_to_file(&quant_model_path)?;
     println!("Quantized model saved to {:?}", quant_model_path);
 
-    let mut fine_trainer = Trainer::new(&mut network, 0.1f32, 0.8f32);
-    fine_trainer.fine_tune(&training_data, 5_000);
+    let mut fine_trainer = Trainer::with_loss(
+        &mut network,
+        0.02f32,
+        0.8f32,
+        CategoricalCrossEntropy::default(),
+    );
+    fine_trainer.batch_size = Some(64);
+    fine_trainer.seed = seed;
+    fine_trainer.fine_tune(&training_data, 5_000)?;
 
     let fine_tuned_model_path = PathBuf::from("model_fine_tuned.bin");
     network.save_to_file(&fine_tuned_model_path)?;
@@ -83,7 +103,7 @@ fn train() -> Result<()> {
         fine_tuned_model_path
     );
 
-    let requantized_network = network.quantize();
+    let requantized_network = quantize(&network)?;
 
     let requant_model_path = PathBuf::from("model_requantized.bin");
     requantized_network.save_to_file(&requant_model_path)?;
@@ -91,6 +111,14 @@ fn train() -> Result<()> {
     Ok(())
 }
 
+/// Quantizes with the default configuration, printing what it had to adjust.
+fn quantize(network: &NeuralNetwork) -> Result<QuantizedNeuralNetwork> {
+    for warning in network.quantization_warnings(&QuantizationConfig::default()) {
+        println!("{}", warning);
+    }
+    network.quantize()
+}
+
 fn eval() -> Result<()> {
     let requant_model_path = PathBuf::from("model_requantized.bin");
     let requantized_network = QuantizedNeuralNetwork::load_from_file(&requant_model_path)?;
@@ -118,19 +146,9 @@ fn eval() -> Result<()> {
 
     let mut correct = 0;
     for point in &test_data {
-        let quant_inputs = point
-            .inputs
-            .mapv(|x| (x * 127.0).round().clamp(-128.0, 127.0) as i8);
+        let quant_inputs = requantized_network.quantize_input(&point.inputs);
 
-        let output = requantized_network.feedforward(&quant_inputs);
-        let output_float = output.mapv(|x| x as f32 / 127.0);
-
-        let prediction = output_float
-            .iter()
-            .enumerate()
-            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-            .map(|(idx, _)| idx)
-            .unwrap();
+        let prediction = requantized_network.predict(&quant_inputs);
 
         let target = point
             .targets
@@ -0,0 +1,297 @@
+use crate::nd::{s, Array1, Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMutD, Axis};
+use crate::{
+    stack_rows, ActivationFunction, ActivationRange, DataPoint, Layer, LayerCache, NetworkLayer,
+    NeuralNetwork, QuantizationConfig, QuantizationParams, QuantizedLayer,
+};
+use rand::rngs::StdRng;
+use ray_shared::result::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Samples pushed through the network at once by [`NeuralNetwork::update_statistics`].
+const STATISTICS_CHUNK: usize = 256;
+
+/// Normalizes every channel, then scales it by `gamma`, shifts it by `beta` and applies
+/// `activation`. Training uses the statistics of the mini-batch, inference `mean` and
+/// `variance`. A batch of a single sample has no spread to normalize, so it is trained with
+/// the inference statistics.
+/// Like [`crate::Conv1d`] outputs, samples are `channels` sequences of `length` values; dense
+/// outputs have `length` 1.
+///
+/// Placed right after a linear dense or conv layer it folds into that layer, see
+/// [`NeuralNetwork::fold_batch_norm`].
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchNorm {
+    pub channels: usize,
+    pub length: usize,
+    pub gamma: Array1<f32>,
+    pub beta: Array1<f32>,
+    /// Inference statistics, see [`NeuralNetwork::update_statistics`].
+    pub mean: Array1<f32>,
+    pub variance: Array1<f32>,
+    /// Added to the variance before taking the square root.
+    pub epsilon: f32,
+    pub activation: ActivationFunction,
+}
+
+impl BatchNorm {
+    pub fn new(channels: usize, length: usize, activation: ActivationFunction) -> Self {
+        assert!(
+            activation != ActivationFunction::Softmax || length == 1,
+            "Softmax normalizes over a whole layer; put a dense layer after the normalization."
+        );
+        BatchNorm {
+            channels,
+            length,
+            gamma: Array1::ones(channels),
+            beta: Array1::zeros(channels),
+            mean: Array1::zeros(channels),
+            variance: Array1::ones(channels),
+            epsilon: 1e-5,
+            activation,
+        }
+    }
+
+    /// Per-channel `scale` and `shift` with `scale * x + shift` equal to the inference
+    /// normalization of `x`.
+    pub fn scale_and_shift(&self) -> (Array1<f32>, Array1<f32>) {
+        let scale = &self.gamma / &self.variance.mapv(|v| (v + self.epsilon).sqrt());
+        let shift = &self.beta - &(&self.mean * &scale);
+        (scale, shift)
+    }
+
+    /// Sets `mean` and `variance` to the statistics of all `inputs` batches together.
+    pub fn set_statistics(&mut self, inputs: impl IntoIterator<Item = Array2<f32>>) {
+        let mut count = 0;
+        let mut sums = vec![0.0f64; self.channels];
+        let mut squares = vec![0.0f64; self.channels];
+
+        for input in inputs {
+            count += input.nrows() * self.length;
+            for (column, values) in input.columns().into_iter().enumerate() {
+                let channel = column / self.length;
+                for &x in values {
+                    sums[channel] += x as f64;
+                    squares[channel] += x as f64 * x as f64;
+                }
+            }
+        }
+        if count == 0 {
+            return;
+        }
+
+        let count = count as f64;
+        self.mean = sums.iter().map(|&sum| (sum / count) as f32).collect();
+        self.variance = sums
+            .iter()
+            .zip(&squares)
+            .map(|(&sum, &square)| {
+                let mean = sum / count;
+                (square / count - mean * mean).max(0.0) as f32
+            })
+            .collect();
+    }
+
+    /// Sums every channel over the samples and positions of `values`.
+    fn channel_sums(&self, values: &Array2<f32>) -> Array1<f32> {
+        let columns = values.sum_axis(Axis(0));
+        Array1::from_shape_fn(self.channels, |channel| {
+            let start = channel * self.length;
+            columns.slice(s![start..start + self.length]).sum()
+        })
+    }
+
+    /// Repeats a per-channel value for every column of a sample.
+    fn per_column(&self, values: &Array1<f32>) -> Array1<f32> {
+        Array1::from_shape_fn(self.channels * self.length, |column| {
+            values[column / self.length]
+        })
+    }
+
+    /// Multiplies the output channels of a layer computing `weights * x + biases` by `scale`
+    /// and adds `shift`.
+    fn fold_into(&self, weights: &mut Array2<f32>, biases: &mut Array1<f32>) {
+        let (scale, shift) = self.scale_and_shift();
+        *weights *= &scale.view().insert_axis(Axis(1));
+        *biases = &*biases * &scale + &shift;
+    }
+}
+
+impl Layer for BatchNorm {
+    fn input_size(&self) -> usize {
+        self.channels * self.length
+    }
+
+    fn output_size(&self) -> usize {
+        self.channels * self.length
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        let (scale, shift) = self.scale_and_shift();
+        let z = &input * &self.per_column(&scale) + &self.per_column(&shift);
+        self.activation.activate_batch(&z)
+    }
+
+    /// Caches the pre-activation, the normalized input and the inverse standard deviation of
+    /// every channel.
+    fn forward_train(&self, input: &Array2<f32>, _rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        let single = input.nrows() < 2;
+        let count = (input.nrows() * self.length) as f32;
+        let mean = if single {
+            self.mean.clone()
+        } else {
+            self.channel_sums(input) / count
+        };
+        let centered = input - &self.per_column(&mean);
+        let variance = if single {
+            self.variance.clone()
+        } else {
+            self.channel_sums(&centered.mapv(|x| x * x)) / count
+        };
+        let inv_std = variance.mapv(|v| 1.0 / (v + self.epsilon).sqrt());
+
+        let normalized = centered * &self.per_column(&inv_std);
+        let z = &normalized * &self.per_column(&self.gamma) + &self.per_column(&self.beta);
+        (
+            self.activation.activate_batch(&z),
+            vec![z, normalized, inv_std.insert_axis(Axis(0))],
+        )
+    }
+
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        let delta = self.activation.backward_batch(&cache[0], output_grad);
+        self.backward_pre_activation(input, cache, &delta, parameter_grads, input_grad)
+    }
+
+    fn output_activation<'c>(
+        &self,
+        cache: &'c LayerCache,
+    ) -> Option<(ActivationFunction, &'c Array2<f32>)> {
+        Some((self.activation, &cache[0]))
+    }
+
+    /// The batch statistics depend on every sample, so each input gradient collects the
+    /// gradients of the whole batch through them. The inference statistics a single sample is
+    /// normalized with are constants.
+    fn backward_pre_activation(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        delta: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        let normalized = &cache[1];
+        let gamma_grad = self.channel_sums(&(delta * normalized));
+        let beta_grad = self.channel_sums(delta);
+        parameter_grads[0] += &gamma_grad;
+        parameter_grads[1] += &beta_grad;
+
+        input_grad.then(|| {
+            let inv_std = cache[2].row(0).to_owned();
+            if input.nrows() < 2 {
+                return delta * &self.per_column(&(&self.gamma * &inv_std));
+            }
+            let count = (input.nrows() * self.length) as f32;
+            let factor = &self.gamma * &inv_std / count;
+            (delta * count
+                - &self.per_column(&beta_grad)
+                - normalized * &self.per_column(&gamma_grad))
+                * &self.per_column(&factor)
+        })
+    }
+
+    fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        vec![self.gamma.view().into_dyn(), self.beta.view().into_dyn()]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![
+            self.gamma.view_mut().into_dyn(),
+            self.beta.view_mut().into_dyn(),
+        ]
+    }
+
+    /// [`NeuralNetwork::quantize_with`] folds normalization away before quantizing.
+    fn quantize(
+        &self,
+        _input: QuantizationParams,
+        _output: ActivationRange,
+        _config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        unreachable!("batch normalization is folded before quantization")
+    }
+}
+
+impl NeuralNetwork {
+    /// Sets the inference statistics of every [`BatchNorm`] to those of its input over
+    /// `data`. Earlier layers go first, so later ones see the final statistics.
+    pub fn update_statistics(&mut self, data: &[DataPoint]) {
+        for layer_idx in 0..self.layers.len() {
+            let (before, rest) = self.layers.split_at_mut(layer_idx);
+            let NetworkLayer::BatchNorm(norm) = &mut rest[0] else {
+                continue;
+            };
+
+            norm.set_statistics(data.chunks(STATISTICS_CHUNK).map(|chunk| {
+                let inputs = stack_rows(chunk.iter().map(|point| &point.inputs));
+                before.iter().fold(inputs, |activations, layer| {
+                    layer.forward(activations.view())
+                })
+            }));
+        }
+    }
+
+    /// Merges every [`BatchNorm`] into the linear dense or conv layer right before it, which
+    /// takes over its activation. The network computes the same function afterwards, without
+    /// a separate normalization stage.
+    pub fn fold_batch_norm(&mut self) -> Result<()> {
+        self.layers = self.folded_layers()?.0;
+        Ok(())
+    }
+
+    /// The layers with every [`BatchNorm`] folded away, and the indices the folded layers had.
+    pub(crate) fn folded_layers(&self) -> Result<(Vec<NetworkLayer>, Vec<usize>)> {
+        let mut layers: Vec<NetworkLayer> = Vec::with_capacity(self.layers.len());
+        let mut folded = Vec::new();
+
+        for (layer_idx, layer) in self.layers.iter().enumerate() {
+            let NetworkLayer::BatchNorm(norm) = layer else {
+                layers.push(layer.clone());
+                continue;
+            };
+
+            match layers.last_mut() {
+                Some(NetworkLayer::Dense(dense))
+                    if dense.activation == ActivationFunction::Linear
+                        && norm.length == 1
+                        && dense.output_size() == norm.channels =>
+                {
+                    norm.fold_into(&mut dense.weights, &mut dense.biases);
+                    dense.activation = norm.activation;
+                }
+                Some(NetworkLayer::Conv1d(conv))
+                    if conv.activation == ActivationFunction::Linear
+                        && conv.out_channels() == norm.channels
+                        && conv.shape.output_length() == norm.length =>
+                {
+                    norm.fold_into(&mut conv.weights, &mut conv.biases);
+                    conv.activation = norm.activation;
+                }
+                _ => bail!(
+                    "Layer {} cannot be folded: batch normalization must directly follow a dense or conv layer with linear activation and the same output shape.",
+                    layer_idx
+                ),
+            }
+            folded.push(layer_idx);
+        }
+
+        Ok((layers, folded))
+    }
+}
@@ -0,0 +1,336 @@
+use crate::nd::{Array1, Axis};
+use crate::{
+    DataPoint, Layer, NeuralNetwork, QuantizationParams, QuantizationScheme,
+    DEFAULT_ACTIVATION_SCALE,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const HISTOGRAM_BINS: usize = 2048;
+
+/// Number of positive levels of a symmetric int8 tensor.
+const QUANTIZED_LEVELS: usize = 128;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub enum CalibrationMethod {
+    /// Use the observed minimum and maximum.
+    MinMax,
+    /// Keep the given percentage of the distribution (e.g. `99.99`), clipping both tails.
+    Percentile(f32),
+    /// Pick the clipping threshold that minimizes the KL divergence between the float
+    /// distribution and its 8 bit counterpart.
+    Entropy,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ActivationRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for ActivationRange {
+    fn default() -> Self {
+        ActivationRange {
+            min: -1.0,
+            max: 1.0,
+        }
+    }
+}
+
+impl ActivationRange {
+    /// Scale mapping the largest magnitude of the range onto 127.
+    pub fn symmetric_scale(&self) -> f32 {
+        let bound = self.min.abs().max(self.max.abs());
+        if bound > 0.0 && bound.is_finite() {
+            bound / 127.0
+        } else {
+            DEFAULT_ACTIVATION_SCALE
+        }
+    }
+
+    pub fn quantization_params(&self, scheme: QuantizationScheme) -> QuantizationParams {
+        match scheme {
+            QuantizationScheme::Symmetric => QuantizationParams {
+                scale: self.symmetric_scale(),
+                zero_point: 0,
+            },
+            QuantizationScheme::Asymmetric => {
+                // Zero has to stay exactly representable (ReLU, padding), so the range covers it.
+                let min = self.min.min(0.0);
+                let max = self.max.max(0.0);
+                let scale = (max - min) / 255.0;
+                if !(scale > 0.0 && scale.is_finite()) {
+                    return QuantizationParams::default();
+                }
+
+                QuantizationParams {
+                    scale,
+                    zero_point: (-128.0 - min / scale).round().clamp(-128.0, 127.0) as i32,
+                }
+            }
+        }
+    }
+}
+
+/// Activation ranges for the network input (index 0) and the output of every layer.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Calibration {
+    pub ranges: Vec<ActivationRange>,
+}
+
+impl Calibration {
+    pub fn uniform(layer_count: usize, range: ActivationRange) -> Self {
+        Calibration {
+            ranges: vec![range; layer_count + 1],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ActivationStatistics {
+    pub min: f32,
+    pub max: f32,
+    pub count: u64,
+    /// Values binned over `[min, max]`.
+    histogram: Vec<u64>,
+    /// Magnitudes binned over `[0, max(|min|, |max|)]`.
+    abs_histogram: Vec<u64>,
+}
+
+impl ActivationStatistics {
+    fn new(min: f32, max: f32) -> Self {
+        ActivationStatistics {
+            min,
+            max,
+            count: 0,
+            histogram: vec![0; HISTOGRAM_BINS],
+            abs_histogram: vec![0; HISTOGRAM_BINS],
+        }
+    }
+
+    fn bin_width(&self) -> f32 {
+        ((self.max - self.min) / HISTOGRAM_BINS as f32).max(f32::MIN_POSITIVE)
+    }
+
+    fn abs_bin_width(&self) -> f32 {
+        (self.min.abs().max(self.max.abs()) / HISTOGRAM_BINS as f32).max(f32::MIN_POSITIVE)
+    }
+
+    fn record(&mut self, values: &Array1<f32>) {
+        let width = self.bin_width();
+        let abs_width = self.abs_bin_width();
+
+        for &x in values {
+            let bin = ((x - self.min) / width) as usize;
+            self.histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+
+            let abs_bin = (x.abs() / abs_width) as usize;
+            self.abs_histogram[abs_bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+
+        self.count += values.len() as u64;
+    }
+
+    fn merge(&mut self, other: &ActivationStatistics) {
+        for (a, b) in self.histogram.iter_mut().zip(&other.histogram) {
+            *a += b;
+        }
+        for (a, b) in self.abs_histogram.iter_mut().zip(&other.abs_histogram) {
+            *a += b;
+        }
+        self.count += other.count;
+    }
+
+    /// Smallest bin edge below which at least `fraction` of the values lie.
+    fn quantile(&self, fraction: f64) -> f32 {
+        let target = (fraction * self.count as f64).ceil() as u64;
+        let mut cumulative = 0;
+
+        for (bin, &count) in self.histogram.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= target {
+                return self.min + bin as f32 * self.bin_width();
+            }
+        }
+
+        self.max
+    }
+
+    pub fn range(&self, method: CalibrationMethod) -> ActivationRange {
+        if self.count == 0 {
+            return ActivationRange::default();
+        }
+
+        match method {
+            CalibrationMethod::MinMax => ActivationRange {
+                min: self.min,
+                max: self.max,
+            },
+            CalibrationMethod::Percentile(percentile) => {
+                let tail = (1.0 - percentile as f64 / 100.0).clamp(0.0, 1.0) / 2.0;
+                ActivationRange {
+                    min: self.quantile(tail).max(self.min),
+                    max: (self.quantile(1.0 - tail) + self.bin_width()).min(self.max),
+                }
+            }
+            CalibrationMethod::Entropy => {
+                let threshold = entropy_threshold(&self.abs_histogram, self.abs_bin_width());
+                ActivationRange {
+                    min: self.min.max(-threshold),
+                    max: self.max.min(threshold),
+                }
+            }
+        }
+    }
+}
+
+/// TensorRT style threshold search: every candidate clips the histogram, folds the clipped
+/// mass into the last bin and is compared against the same bins squeezed into 128 levels.
+fn entropy_threshold(histogram: &[u64], bin_width: f32) -> f32 {
+    let mut best = (f64::INFINITY, histogram.len());
+
+    for end in QUANTIZED_LEVELS..=histogram.len() {
+        let mut reference: Vec<f64> = histogram[..end].iter().map(|&c| c as f64).collect();
+        reference[end - 1] += histogram[end..].iter().sum::<u64>() as f64;
+
+        let mut candidate = vec![0.0f64; end];
+        let merge = end as f64 / QUANTIZED_LEVELS as f64;
+
+        for level in 0..QUANTIZED_LEVELS {
+            let start = (level as f64 * merge).floor() as usize;
+            let stop = if level == QUANTIZED_LEVELS - 1 {
+                end
+            } else {
+                ((level + 1) as f64 * merge).floor() as usize
+            };
+
+            // Squeezed from the clipped histogram, so the folded mass is kept too.
+            let bins = &reference[start..stop];
+            let nonzero = bins.iter().filter(|&&c| c != 0.0).count();
+            if nonzero == 0 {
+                continue;
+            }
+
+            let share = bins.iter().sum::<f64>() / nonzero as f64;
+            for (slot, &count) in candidate[start..stop].iter_mut().zip(bins) {
+                if count != 0.0 {
+                    *slot = share;
+                }
+            }
+        }
+
+        let divergence = kl_divergence(&reference, &candidate);
+        if divergence < best.0 {
+            best = (divergence, end);
+        }
+    }
+
+    (best.1 as f32 + 0.5) * bin_width
+}
+
+fn kl_divergence(p: &[f64], q: &[f64]) -> f64 {
+    let p_sum: f64 = p.iter().sum();
+    let q_sum: f64 = q.iter().sum();
+    if p_sum == 0.0 || q_sum == 0.0 {
+        return f64::INFINITY;
+    }
+
+    let mut divergence = 0.0;
+    for (&p, &q) in p.iter().zip(q) {
+        if p == 0.0 {
+            continue;
+        }
+        if q == 0.0 {
+            return f64::INFINITY;
+        }
+        let (p, q) = (p / p_sum, q / q_sum);
+        divergence += p * (p / q).ln();
+    }
+
+    divergence
+}
+
+impl NeuralNetwork {
+    /// The input followed by the output of every layer.
+    fn layer_outputs(&self, input: &Array1<f32>) -> Vec<Array1<f32>> {
+        let mut outputs = vec![input.clone()];
+
+        for layer in &self.layers {
+            let input = outputs.last().unwrap().view().insert_axis(Axis(0));
+            let output = layer.forward(input).remove_axis(Axis(0));
+            outputs.push(output);
+        }
+
+        outputs
+    }
+
+    /// Runs the float network over `data` and records statistics for the input and the output
+    /// of every layer.
+    pub fn activation_statistics(&self, data: &[DataPoint]) -> Vec<ActivationStatistics> {
+        let tensor_count = self.layers.len() + 1;
+
+        // Histograms need fixed bounds, so the first pass only looks for them.
+        let bounds = data
+            .par_iter()
+            .map(|point| self.layer_outputs(&point.inputs))
+            .fold(
+                || vec![(f32::INFINITY, f32::NEG_INFINITY); tensor_count],
+                |mut bounds, outputs| {
+                    for ((min, max), output) in bounds.iter_mut().zip(&outputs) {
+                        for &x in output {
+                            *min = min.min(x);
+                            *max = max.max(x);
+                        }
+                    }
+                    bounds
+                },
+            )
+            .reduce(
+                || vec![(f32::INFINITY, f32::NEG_INFINITY); tensor_count],
+                |a, b| {
+                    a.iter()
+                        .zip(&b)
+                        .map(|(a, b)| (a.0.min(b.0), a.1.max(b.1)))
+                        .collect()
+                },
+            );
+
+        let empty: Vec<ActivationStatistics> = bounds
+            .into_iter()
+            .map(|(min, max)| ActivationStatistics::new(min, max))
+            .collect();
+
+        data.par_iter()
+            .map(|point| self.layer_outputs(&point.inputs))
+            .fold(
+                || empty.clone(),
+                |mut statistics, outputs| {
+                    for (stats, output) in statistics.iter_mut().zip(&outputs) {
+                        stats.record(output);
+                    }
+                    statistics
+                },
+            )
+            .reduce(
+                || empty.clone(),
+                |mut a, b| {
+                    for (a, b) in a.iter_mut().zip(&b) {
+                        a.merge(b);
+                    }
+                    a
+                },
+            )
+    }
+
+    /// Chooses activation ranges from the network's behaviour on `data`, for use with
+    /// [`NeuralNetwork::quantize_calibrated`].
+    pub fn calibrate(&self, data: &[DataPoint], method: CalibrationMethod) -> Calibration {
+        Calibration {
+            ranges: self
+                .activation_statistics(data)
+                .iter()
+                .map(|stats| stats.range(method))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{Evaluation, QuantizationWarning};
+use ray_shared::result::Result;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+pub enum TrainingPhase {
+    Train,
+    FineTune,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct EpochMetrics {
+    pub phase: TrainingPhase,
+    /// Epoch number, counted across `train` and `fine_tune` calls.
+    pub epoch: usize,
+    /// Average training loss over the epoch, without the weight penalty.
+    pub loss: f32,
+    /// Weight penalty of the [`crate::Trainer::regularization`], averaged over the epoch's
+    /// updates.
+    pub regularization: f32,
+    /// Set on the epochs the validation data was evaluated.
+    pub validation: Option<Evaluation>,
+    /// Learning rate of the epoch's last update.
+    pub learning_rate: f32,
+    /// L2 norm of the averaged gradient, averaged over the epoch's updates.
+    pub gradient_norm: f32,
+    pub epoch_seconds: f64,
+    /// Time since the start of the current `train` or `fine_tune` call.
+    pub elapsed_seconds: f64,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct TrainingSummary {
+    pub phase: TrainingPhase,
+    /// Epochs run by this call.
+    pub epochs: usize,
+    /// Epoch of the best validation when early stopping is enabled.
+    pub best_epoch: Option<usize>,
+    pub stopped_early: bool,
+    pub last: Option<EpochMetrics>,
+}
+
+/// Receives training progress. Errors abort training and are returned from `train` or
+/// `fine_tune`.
+pub trait Callback: Send + Sync {
+    fn on_epoch_end(&mut self, metrics: &EpochMetrics) -> Result<()>;
+
+    fn on_train_end(&mut self, _summary: &TrainingSummary) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_warning(&mut self, _warning: &QuantizationWarning) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Prints progress to stdout every `interval` epochs and whenever validation ran.
+#[derive(Clone, Copy, Debug)]
+pub struct ConsoleLogger {
+    pub interval: usize,
+}
+
+impl Default for ConsoleLogger {
+    fn default() -> Self {
+        ConsoleLogger { interval: 5 }
+    }
+}
+
+impl ConsoleLogger {
+    fn print(metrics: &EpochMetrics) {
+        let prefix = match metrics.phase {
+            TrainingPhase::Train => "",
+            TrainingPhase::FineTune => "Fine-Tuning ",
+        };
+        let mut line = format!(
+            "{}Epoch {}: Error = {}",
+            prefix, metrics.epoch, metrics.loss
+        );
+        if metrics.regularization != 0.0 {
+            line += &format!(", Regularization = {}", metrics.regularization);
+        }
+        if let Some(validation) = metrics.validation {
+            line += &format!(
+                ", Validation Loss = {}, Accuracy = {}",
+                validation.loss, validation.accuracy
+            );
+        }
+        println!("{}", line);
+    }
+}
+
+impl Callback for ConsoleLogger {
+    fn on_epoch_end(&mut self, metrics: &EpochMetrics) -> Result<()> {
+        if metrics.epoch.is_multiple_of(self.interval.max(1)) || metrics.validation.is_some() {
+            Self::print(metrics);
+        }
+        Ok(())
+    }
+
+    fn on_train_end(&mut self, summary: &TrainingSummary) -> Result<()> {
+        if let Some(last) = &summary.last {
+            if !last.epoch.is_multiple_of(self.interval.max(1)) && last.validation.is_none() {
+                Self::print(last);
+            }
+        }
+        if let (true, Some(best_epoch)) = (summary.stopped_early, summary.best_epoch) {
+            println!("Stopped early, best epoch was {}", best_epoch);
+        }
+        Ok(())
+    }
+
+    fn on_warning(&mut self, warning: &QuantizationWarning) -> Result<()> {
+        println!("{}", warning);
+        Ok(())
+    }
+}
+
+/// Writes one row per epoch; the validation columns stay empty on epochs without validation.
+pub struct CsvLogger<W: Write + Send + Sync> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write + Send + Sync> CsvLogger<W> {
+    pub fn new(writer: W) -> Self {
+        CsvLogger {
+            writer,
+            header_written: false,
+        }
+    }
+}
+
+impl CsvLogger<BufWriter<File>> {
+    pub fn create(path: &PathBuf) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Send + Sync> Callback for CsvLogger<W> {
+    fn on_epoch_end(&mut self, metrics: &EpochMetrics) -> Result<()> {
+        if !self.header_written {
+            writeln!(
+                self.writer,
+                "phase,epoch,loss,regularization,validation_loss,validation_accuracy,learning_rate,gradient_norm,epoch_seconds,elapsed_seconds"
+            )?;
+            self.header_written = true;
+        }
+
+        let (validation_loss, validation_accuracy) = match metrics.validation {
+            Some(validation) => (validation.loss.to_string(), validation.accuracy.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writeln!(
+            self.writer,
+            "{:?},{},{},{},{},{},{},{},{},{}",
+            metrics.phase,
+            metrics.epoch,
+            metrics.loss,
+            metrics.regularization,
+            validation_loss,
+            validation_accuracy,
+            metrics.learning_rate,
+            metrics.gradient_norm,
+            metrics.epoch_seconds,
+            metrics.elapsed_seconds
+        )?;
+        Ok(())
+    }
+
+    fn on_train_end(&mut self, _summary: &TrainingSummary) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes every [`EpochMetrics`] as one JSON object per line.
+pub struct JsonLinesLogger<W: Write + Send + Sync> {
+    writer: W,
+}
+
+impl<W: Write + Send + Sync> JsonLinesLogger<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesLogger { writer }
+    }
+}
+
+impl JsonLinesLogger<BufWriter<File>> {
+    pub fn create(path: &PathBuf) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Send + Sync> Callback for JsonLinesLogger<W> {
+    fn on_epoch_end(&mut self, metrics: &EpochMetrics) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, metrics)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn on_train_end(&mut self, _summary: &TrainingSummary) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
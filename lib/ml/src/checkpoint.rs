@@ -0,0 +1,77 @@
+use crate::{
+    load_magic_only, save_with_magic, EarlyStoppingState, NeuralNetwork, OptimizerState, Trainer,
+};
+use ray_shared::result::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Start of checkpoint files; the last byte is the version of the layout.
+const CHECKPOINT_FILE_MAGIC: [u8; 8] = *b"RAYCK\0\0\x01";
+
+/// Everything needed to continue a training run where it stopped.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Checkpoint {
+    pub network: NeuralNetwork,
+    pub optimizer: OptimizerState,
+    pub schedule: Vec<f64>,
+    /// Mini-batch shuffles are derived from the seed and the epoch, so together they are the
+    /// position of the generator.
+    pub seed: u64,
+    pub epoch: usize,
+    pub step: usize,
+    /// Best evaluation so far, so a resumed run keeps counting its patience and can still
+    /// restore the best weights.
+    pub early_stopping: Option<EarlyStoppingState>,
+}
+
+impl Checkpoint {
+    /// Writes to a temporary file first, so a crash while saving keeps the previous
+    /// checkpoint intact.
+    pub fn save_to_file(&self, path: &PathBuf) -> Result<()> {
+        let partial = path.with_extension("partial");
+        save_with_magic(&partial, CHECKPOINT_FILE_MAGIC, self)?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &PathBuf) -> Result<Self> {
+        load_magic_only(path, CHECKPOINT_FILE_MAGIC, "checkpoint")
+    }
+}
+
+impl Trainer<'_> {
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            network: self.network.clone(),
+            optimizer: self.optimizer.state(),
+            schedule: self.schedule.state(),
+            seed: self.seed,
+            epoch: self.epoch,
+            step: self.step,
+            early_stopping: self.early_stopping_state.clone(),
+        }
+    }
+
+    /// Restores the network and the training state. The trainer has to be configured with the
+    /// same optimizer, schedule and hyperparameters as the run that wrote the checkpoint;
+    /// calling `train` again then continues exactly where that run stopped.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        self.optimizer.load_state(checkpoint.optimizer)?;
+        self.schedule.load_state(&checkpoint.schedule)?;
+        *self.network = checkpoint.network;
+        self.seed = checkpoint.seed;
+        self.epoch = checkpoint.epoch;
+        self.step = checkpoint.step;
+        self.early_stopping_state = checkpoint.early_stopping;
+        Ok(())
+    }
+
+    pub fn save_checkpoint(&self, path: &PathBuf) -> Result<()> {
+        self.checkpoint().save_to_file(path)
+    }
+
+    pub fn resume_from_file(&mut self, path: &PathBuf) -> Result<()> {
+        self.resume(Checkpoint::load_from_file(path)?)
+    }
+}
@@ -0,0 +1,320 @@
+use crate::dense::{quantize_weights, smallest_weight_scale};
+use crate::matmul::{matmul, matmul_add};
+use crate::nd::{Array1, Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMutD, Axis};
+use crate::{
+    matrix_grad, ActivationFunction, ActivationRange, Initializer, Layer, LayerCache,
+    QuantizationConfig, QuantizationParams, QuantizedDense, QuantizedLayer,
+};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+/// Input geometry of a [`Conv1d`]. Samples are `in_channels` sequences of `input_length`
+/// values, stored one channel after the other.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Conv1dShape {
+    pub in_channels: usize,
+    pub input_length: usize,
+    pub kernel_size: usize,
+    pub stride: usize,
+    /// Zeros added before the first and after the last value of every channel.
+    pub padding: usize,
+    /// Distance between the inputs read by neighbouring kernel taps.
+    pub dilation: usize,
+}
+
+impl Conv1dShape {
+    /// Stride and dilation 1 without padding.
+    pub fn new(in_channels: usize, input_length: usize, kernel_size: usize) -> Self {
+        Conv1dShape {
+            in_channels,
+            input_length,
+            kernel_size,
+            stride: 1,
+            padding: 0,
+            dilation: 1,
+        }
+    }
+
+    /// Input positions covered by one kernel application.
+    fn span(&self) -> usize {
+        self.dilation * (self.kernel_size - 1) + 1
+    }
+
+    pub fn output_length(&self) -> usize {
+        (self.input_length + 2 * self.padding - self.span()) / self.stride + 1
+    }
+
+    /// Input position read by kernel tap `tap` at output position `position`, `None` inside
+    /// the padding.
+    fn source(&self, position: usize, tap: usize) -> Option<usize> {
+        (position * self.stride + tap * self.dilation)
+            .checked_sub(self.padding)
+            .filter(|&source| source < self.input_length)
+    }
+
+    /// Unfolds a batch into one row per sample and output position, holding every input the
+    /// kernel reads there, input channel major. Padding reads as `pad`.
+    pub fn columns<T: Copy>(&self, input: ArrayView2<T>, pad: T) -> Array2<T> {
+        let length = self.output_length();
+        Array2::from_shape_fn(
+            (input.nrows() * length, self.in_channels * self.kernel_size),
+            |(row, column)| {
+                let (sample, position) = (row / length, row % length);
+                let (channel, tap) = (column / self.kernel_size, column % self.kernel_size);
+                match self.source(position, tap) {
+                    Some(source) => input[[sample, channel * self.input_length + source]],
+                    None => pad,
+                }
+            },
+        )
+    }
+
+    /// Adds every column entry back onto the input it was read from, the reverse of
+    /// [`Conv1dShape::columns`].
+    fn fold_columns(&self, columns: &Array2<f32>, samples: usize) -> Array2<f32> {
+        let length = self.output_length();
+        let mut input = Array2::zeros((samples, self.in_channels * self.input_length));
+
+        for ((row, column), &value) in columns.indexed_iter() {
+            let (sample, position) = (row / length, row % length);
+            let (channel, tap) = (column / self.kernel_size, column % self.kernel_size);
+            if let Some(source) = self.source(position, tap) {
+                input[[sample, channel * self.input_length + source]] += value;
+            }
+        }
+
+        input
+    }
+}
+
+/// Rows of one sample and output position with one column per channel, reordered into one row
+/// per sample holding every channel in turn.
+fn positions_to_samples(positions: Array2<f32>, samples: usize) -> Array2<f32> {
+    let (rows, channels) = positions.dim();
+    let length = rows / samples;
+    positions
+        .into_shape_with_order((samples, length, channels))
+        .expect("positions are in standard layout")
+        .permuted_axes([0, 2, 1])
+        .as_standard_layout()
+        .into_owned()
+        .into_shape_with_order((samples, channels * length))
+        .expect("standard layout")
+}
+
+/// The reverse of [`positions_to_samples`].
+fn samples_to_positions(samples: &Array2<f32>, channels: usize) -> Array2<f32> {
+    let (count, width) = samples.dim();
+    let length = width / channels;
+    samples
+        .as_standard_layout()
+        .into_shape_with_order((count, channels, length))
+        .expect("standard layout")
+        .permuted_axes([0, 2, 1])
+        .as_standard_layout()
+        .into_owned()
+        .into_shape_with_order((count * length, channels))
+        .expect("standard layout")
+}
+
+/// One-dimensional convolution over multi-channel sequences. The output holds
+/// `out_channels` sequences of [`Conv1dShape::output_length`] values, one after the other.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Conv1d {
+    pub shape: Conv1dShape,
+    /// One row per output channel holding its kernel, input channel major.
+    pub weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    pub activation: ActivationFunction,
+}
+
+impl Conv1d {
+    pub fn new(
+        shape: Conv1dShape,
+        out_channels: usize,
+        activation: ActivationFunction,
+        rng: &mut StdRng,
+    ) -> Self {
+        Self::with_initializer(shape, out_channels, activation, &Initializer::Auto, rng)
+    }
+
+    pub fn with_initializer(
+        shape: Conv1dShape,
+        out_channels: usize,
+        activation: ActivationFunction,
+        initializer: &Initializer,
+        rng: &mut StdRng,
+    ) -> Self {
+        assert!(
+            shape.kernel_size > 0 && shape.stride > 0 && shape.dilation > 0,
+            "Kernel size, stride and dilation must be positive."
+        );
+        assert!(
+            shape.span() <= shape.input_length + 2 * shape.padding,
+            "The kernel must fit into the padded input."
+        );
+        assert_ne!(
+            activation,
+            ActivationFunction::Softmax,
+            "Softmax normalizes over a whole layer; put a dense layer after the convolution."
+        );
+
+        let fan_in = shape.in_channels * shape.kernel_size;
+        Conv1d {
+            shape,
+            weights: initializer.weights(
+                (out_channels, fan_in),
+                fan_in,
+                out_channels * shape.kernel_size,
+                activation,
+                rng,
+            ),
+            biases: Array1::zeros(out_channels),
+            activation,
+        }
+    }
+
+    pub fn out_channels(&self) -> usize {
+        self.weights.nrows()
+    }
+
+    /// Pre-activation of `samples` samples from their unfolded input.
+    fn pre_activation(&self, columns: &Array2<f32>, samples: usize) -> Array2<f32> {
+        positions_to_samples(
+            matmul(columns.view(), self.weights.t()) + &self.biases,
+            samples,
+        )
+    }
+}
+
+impl Layer for Conv1d {
+    fn input_size(&self) -> usize {
+        self.shape.in_channels * self.shape.input_length
+    }
+
+    fn output_size(&self) -> usize {
+        self.out_channels() * self.shape.output_length()
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        let columns = self.shape.columns(input, 0.0);
+        let z = self.pre_activation(&columns, input.nrows());
+        self.activation.activate_batch(&z)
+    }
+
+    /// Caches the pre-activation and the unfolded input.
+    fn forward_train(&self, input: &Array2<f32>, _rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        let columns = self.shape.columns(input.view(), 0.0);
+        let z = self.pre_activation(&columns, input.nrows());
+        (self.activation.activate_batch(&z), vec![z, columns])
+    }
+
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        let delta = self.activation.backward_batch(&cache[0], output_grad);
+        self.backward_pre_activation(input, cache, &delta, parameter_grads, input_grad)
+    }
+
+    fn output_activation<'c>(
+        &self,
+        cache: &'c LayerCache,
+    ) -> Option<(ActivationFunction, &'c Array2<f32>)> {
+        Some((self.activation, &cache[0]))
+    }
+
+    fn backward_pre_activation(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        delta: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        let delta = samples_to_positions(delta, self.out_channels());
+        matmul_add(
+            delta.t(),
+            cache[1].view(),
+            matrix_grad(&mut parameter_grads[0]),
+        );
+        parameter_grads[1] += &delta.sum_axis(Axis(0));
+        input_grad.then(|| {
+            self.shape
+                .fold_columns(&matmul(delta.view(), self.weights.view()), input.nrows())
+        })
+    }
+
+    fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        vec![
+            self.weights.view().into_dyn(),
+            self.biases.view().into_dyn(),
+        ]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![
+            self.weights.view_mut().into_dyn(),
+            self.biases.view_mut().into_dyn(),
+        ]
+    }
+
+    fn penalized_parameters(&self) -> Vec<usize> {
+        vec![0]
+    }
+
+    fn quantize(
+        &self,
+        input: QuantizationParams,
+        output: ActivationRange,
+        config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        Some(QuantizedLayer::Conv1d(QuantizedConv1d {
+            shape: self.shape,
+            kernel: quantize_weights(
+                &self.weights,
+                &self.biases,
+                self.activation,
+                input,
+                output,
+                config,
+            ),
+        }))
+    }
+
+    fn smallest_weight_scale(&self, config: &QuantizationConfig) -> Option<f32> {
+        Some(smallest_weight_scale(&self.weights, config))
+    }
+}
+
+/// Integer counterpart of [`Conv1d`].
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QuantizedConv1d {
+    pub shape: Conv1dShape,
+    /// The kernels of all output channels, applied at every output position like a dense
+    /// layer.
+    pub kernel: QuantizedDense,
+}
+
+impl QuantizedConv1d {
+    pub fn feedforward(&self, input: &Array1<i8>) -> Array1<i8> {
+        // The zero point stands for a real zero, so the zero-point correction cancels padding.
+        let pad = self.kernel.input.zero_point as i8;
+        let columns = self.shape.columns(input.view().insert_axis(Axis(0)), pad);
+
+        let length = columns.nrows();
+        let mut output = Array1::zeros(self.kernel.weights.nrows() * length);
+        for (position, column) in columns.rows().into_iter().enumerate() {
+            let values = self.kernel.feedforward(&column.to_owned());
+            for (channel, &value) in values.iter().enumerate() {
+                output[channel * length + position] = value;
+            }
+        }
+
+        output
+    }
+}
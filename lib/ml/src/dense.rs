@@ -0,0 +1,286 @@
+use crate::matmul::{matmul, matmul_add};
+use crate::nd::{Array1, Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMutD, Axis, Zip};
+use crate::{
+    matrix_grad, ActivationFunction, ActivationRange, Initializer, Layer, LayerCache,
+    QuantizationConfig, QuantizationParams, QuantizedDense, QuantizedLayer, Requantization,
+    WeightGranularity, MIN_WEIGHT_SCALE, SOFTMAX_INPUT_SCALE,
+};
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+/// Fully connected layer: `activation(weights * x + biases)`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Dense {
+    pub weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    pub activation: ActivationFunction,
+}
+
+impl Dense {
+    pub fn new(
+        input_size: usize,
+        output_size: usize,
+        activation: ActivationFunction,
+        rng: &mut StdRng,
+    ) -> Self {
+        Self::with_initializer(input_size, output_size, activation, &Initializer::Auto, rng)
+    }
+
+    pub fn with_initializer(
+        input_size: usize,
+        output_size: usize,
+        activation: ActivationFunction,
+        initializer: &Initializer,
+        rng: &mut StdRng,
+    ) -> Self {
+        Dense {
+            weights: initializer.weights(
+                (output_size, input_size),
+                input_size,
+                output_size,
+                activation,
+                rng,
+            ),
+            biases: Array1::zeros(output_size),
+            activation,
+        }
+    }
+
+    fn pre_activation(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        matmul(input, self.weights.t()) + &self.biases
+    }
+}
+
+impl Layer for Dense {
+    fn input_size(&self) -> usize {
+        self.weights.ncols()
+    }
+
+    fn output_size(&self) -> usize {
+        self.weights.nrows()
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        self.activation.activate_batch(&self.pre_activation(input))
+    }
+
+    /// Caches the pre-activation.
+    fn forward_train(&self, input: &Array2<f32>, _rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        let z = self.pre_activation(input.view());
+        (self.activation.activate_batch(&z), vec![z])
+    }
+
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        let delta = self.activation.backward_batch(&cache[0], output_grad);
+        self.backward_pre_activation(input, cache, &delta, parameter_grads, input_grad)
+    }
+
+    fn output_activation<'c>(
+        &self,
+        cache: &'c LayerCache,
+    ) -> Option<(ActivationFunction, &'c Array2<f32>)> {
+        Some((self.activation, &cache[0]))
+    }
+
+    fn backward_pre_activation(
+        &self,
+        input: &Array2<f32>,
+        _cache: &LayerCache,
+        delta: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        matmul_add(
+            delta.t(),
+            input.view(),
+            matrix_grad(&mut parameter_grads[0]),
+        );
+        parameter_grads[1] += &delta.sum_axis(Axis(0));
+        input_grad.then(|| matmul(delta.view(), self.weights.view()))
+    }
+
+    fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        vec![
+            self.weights.view().into_dyn(),
+            self.biases.view().into_dyn(),
+        ]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![
+            self.weights.view_mut().into_dyn(),
+            self.biases.view_mut().into_dyn(),
+        ]
+    }
+
+    fn penalized_parameters(&self) -> Vec<usize> {
+        vec![0]
+    }
+
+    fn quantize(
+        &self,
+        input: QuantizationParams,
+        output: ActivationRange,
+        config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        Some(QuantizedLayer::Dense(quantize_weights(
+            &self.weights,
+            &self.biases,
+            self.activation,
+            input,
+            output,
+            config,
+        )))
+    }
+
+    fn smallest_weight_scale(&self, config: &QuantizationConfig) -> Option<f32> {
+        Some(smallest_weight_scale(&self.weights, config))
+    }
+}
+
+/// Normally distributed weights scaled for `activation`: He for the ReLU family, Glorot
+/// otherwise. This is [`Initializer::Auto`].
+pub(crate) fn random_weights(
+    shape: (usize, usize),
+    fan_in: usize,
+    fan_out: usize,
+    activation: ActivationFunction,
+    rng: &mut StdRng,
+) -> Array2<f32> {
+    let std_dev = match activation {
+        ActivationFunction::Sigmoid
+        | ActivationFunction::Linear
+        | ActivationFunction::Softmax
+        | ActivationFunction::Tanh
+        | ActivationFunction::HardSigmoid => ((2.0) / (fan_in + fan_out) as f32).sqrt(),
+        ActivationFunction::ReLU
+        | ActivationFunction::LeakyReLU(_)
+        | ActivationFunction::ClippedReLU(_)
+        | ActivationFunction::HardSwish
+        | ActivationFunction::SiLU
+        | ActivationFunction::GELU => (2.0 / fan_in as f32).sqrt(),
+    };
+    let normal = Normal::new(0.0, std_dev).unwrap();
+    Array2::from_shape_fn(shape, |_| normal.sample(rng))
+}
+
+/// Largest absolute weight of every row, or of the whole matrix repeated per row.
+fn max_weights(weights: &Array2<f32>, granularity: WeightGranularity) -> Array1<f32> {
+    match granularity {
+        WeightGranularity::PerTensor => {
+            let max_weight = weights.mapv(f32::abs).fold(0.0f32, |a, b| a.max(*b));
+            Array1::from_elem(weights.nrows(), max_weight)
+        }
+        WeightGranularity::PerChannel => {
+            weights.map_axis(Axis(1), |row| row.fold(0.0f32, |a, b| a.max(b.abs())))
+        }
+    }
+}
+
+pub(crate) fn smallest_weight_scale(weights: &Array2<f32>, config: &QuantizationConfig) -> f32 {
+    max_weights(weights, config.weight_granularity)
+        .iter()
+        .filter(|&&max_weight| max_weight > 0.0)
+        .fold(f32::INFINITY, |a, &b| a.min(b / 127.0))
+}
+
+/// Integer version of `activation(weights * x + biases)` reading `input` and producing
+/// `output`, the calibrated range of the result.
+pub(crate) fn quantize_weights(
+    weights: &Array2<f32>,
+    biases: &Array1<f32>,
+    activation: ActivationFunction,
+    input: QuantizationParams,
+    output: ActivationRange,
+    config: &QuantizationConfig,
+) -> QuantizedDense {
+    // Enforce a minimum weight_scale to prevent quantized weights from being all zeros
+    let weight_scales = max_weights(weights, config.weight_granularity).mapv(|max_weight| {
+        if max_weight == 0.0 {
+            1.0
+        } else {
+            (max_weight / 127.0).max(MIN_WEIGHT_SCALE)
+        }
+    });
+
+    let row_scales = weight_scales.view().insert_axis(Axis(1));
+    let quant_weights = Zip::from(weights)
+        .and_broadcast(&row_scales)
+        .map_collect(|&weight, &scale| (weight / scale).round().clamp(-128.0, 127.0) as i8);
+
+    // The accumulator holds sum(w_q * x_q), so biases have to live on the same scale.
+    let bias_scales = &weight_scales * input.scale;
+
+    let quant_biases = Zip::from(biases)
+        .and(&bias_scales)
+        .map_collect(|&bias, &bias_scale| {
+            let scaled_bias = bias / bias_scale;
+            if scaled_bias.is_finite() {
+                scaled_bias.round() as i32
+            } else if bias > 0.0 {
+                i32::MAX
+            } else {
+                i32::MIN
+            }
+        });
+
+    // sum(w_q * (x_q - zp)) expands to sum(w_q * x_q) - zp * sum(w_q).
+    let zero_point_corrections = quant_weights
+        .map_axis(Axis(1), |row| row.iter().map(|&w| w as i32).sum::<i32>())
+        * -input.zero_point;
+
+    // Bounded outputs have a known range, so calibration is not needed for them.
+    let range = activation.fixed_range().unwrap_or(output);
+    let output = range.quantization_params(config.activation_scheme);
+
+    // Sigmoid layers requantize into the LUT input domain instead of the output scale.
+    let (requant_target, activation_table) = match activation {
+        ActivationFunction::Sigmoid => (
+            config.sigmoid_lut.input_scale(),
+            config.sigmoid_lut.table(output),
+        ),
+        // Logits of all neurons have to share one grid to be compared against each other.
+        ActivationFunction::Softmax => (SOFTMAX_INPUT_SCALE, Vec::new()),
+        ActivationFunction::ReLU
+        | ActivationFunction::Linear
+        | ActivationFunction::LeakyReLU(_)
+        | ActivationFunction::ClippedReLU(_) => (output.scale, Vec::new()),
+        // The other curves are looked up on a symmetric grid covering their interesting part.
+        ActivationFunction::Tanh
+        | ActivationFunction::HardSigmoid
+        | ActivationFunction::HardSwish
+        | ActivationFunction::SiLU
+        | ActivationFunction::GELU => {
+            let input_scale = activation.table_bound(range) / 127.0;
+            (input_scale, activation.lookup_table(input_scale, output))
+        }
+    };
+
+    let requantizations = bias_scales
+        .iter()
+        .map(|&bias_scale| Requantization::from_scale(bias_scale / requant_target))
+        .collect();
+
+    QuantizedDense {
+        weights: quant_weights,
+        biases: quant_biases,
+        activation,
+        weight_granularity: config.weight_granularity,
+        weight_scales,
+        bias_scales,
+        zero_point_corrections,
+        input,
+        output,
+        requantizations,
+        activation_table,
+        sigmoid_lut: config.sigmoid_lut,
+    }
+}
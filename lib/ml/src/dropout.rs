@@ -0,0 +1,72 @@
+use crate::nd::{Array2, ArrayD, ArrayView2};
+use crate::{
+    ActivationRange, Layer, LayerCache, QuantizationConfig, QuantizationParams, QuantizedLayer,
+};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Zeroes every value with probability `rate` while a [`crate::Trainer`] runs and scales the
+/// rest by `1 / (1 - rate)`, so inference passes values through unchanged.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Dropout {
+    pub size: usize,
+    pub rate: f32,
+}
+
+impl Dropout {
+    pub fn new(size: usize, rate: f32) -> Self {
+        assert!(
+            (0.0..1.0).contains(&rate),
+            "The dropout rate must be in [0, 1)."
+        );
+        Dropout { size, rate }
+    }
+}
+
+impl Layer for Dropout {
+    fn input_size(&self) -> usize {
+        self.size
+    }
+
+    fn output_size(&self) -> usize {
+        self.size
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        input.to_owned()
+    }
+
+    /// Caches the mask, holding `0` for dropped values and the scale for kept ones.
+    fn forward_train(&self, input: &Array2<f32>, rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        let scale = 1.0 / (1.0 - self.rate);
+        let mask = Array2::from_shape_simple_fn(input.raw_dim(), || {
+            if rng.gen::<f32>() < self.rate {
+                0.0
+            } else {
+                scale
+            }
+        });
+        (input * &mask, vec![mask])
+    }
+
+    fn backward(
+        &self,
+        _input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        _parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        input_grad.then(|| output_grad * &cache[0])
+    }
+
+    fn quantize(
+        &self,
+        _input: QuantizationParams,
+        _output: ActivationRange,
+        _config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        None
+    }
+}
@@ -0,0 +1,105 @@
+use crate::nd::ArrayD;
+use crate::{stack_rows, DataPoint, Layer, NeuralNetwork, Regularization, Trainer};
+use ray_shared::result::{bail, Result};
+
+/// Analytical against numerical gradients, see [`Trainer::check_gradients`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct GradientCheck {
+    /// Largest relative error among the parameter tensors of every layer; 0 for layers
+    /// without any.
+    pub layers: Vec<f32>,
+}
+
+impl GradientCheck {
+    pub fn max_error(&self) -> f32 {
+        self.layers.iter().fold(0.0, |a, &b| a.max(b))
+    }
+}
+
+impl Trainer<'_> {
+    /// Compares the gradients training hands to the optimizer for `data` as one batch, those
+    /// of the mean loss plus the [`Regularization`] penalties, with central differences of
+    /// step `epsilon`. The error of a parameter tensor is relative, the norm of the difference
+    /// over the larger of the two gradient norms or 1e-6. Single entries near zero would
+    /// otherwise only measure the rounding of `f32` losses.
+    ///
+    /// Random layers such as [`crate::Dropout`] see the same draws in every pass, and layers
+    /// using batch statistics the statistics of `data`, which must not be empty.
+    pub fn check_gradients(&self, data: &[DataPoint], epsilon: f32) -> Result<GradientCheck> {
+        if data.is_empty() {
+            bail!("Gradients cannot be checked without data.");
+        }
+
+        let indices: Vec<usize> = (0..data.len()).collect();
+        let mut gradients = self.zero_gradients();
+        self.compute_gradients(data, &indices, &mut gradients);
+        let (analytical, _) = gradients;
+        let penalties = self.parameter_penalties();
+
+        let mut network = self.network.clone();
+        let mut analytical = analytical.into_iter().zip(&penalties);
+        let mut layers = Vec::with_capacity(network.layers.len());
+
+        for layer_idx in 0..network.layers.len() {
+            let mut max_error = 0.0f32;
+            for parameter_idx in 0..network.layers[layer_idx].parameters().len() {
+                let (gradient, regularization) = analytical.next().unwrap();
+                let mut gradient = gradient / data.len() as f32;
+                if let Some(regularization) = regularization {
+                    let parameter = &network.layers[layer_idx].parameters()[parameter_idx];
+                    gradient += &regularization.gradient(parameter);
+                }
+
+                let mut numerical = ArrayD::zeros(gradient.raw_dim());
+                for (index, numerical) in numerical.indexed_iter_mut() {
+                    let original = network.layers[layer_idx].parameters()[parameter_idx][&index];
+                    let mut objective_at = |value: f32| {
+                        network.layers[layer_idx].parameters_mut()[parameter_idx][&index] = value;
+                        self.objective(&network, data, &penalties)
+                    };
+                    *numerical = (objective_at(original + epsilon)
+                        - objective_at(original - epsilon))
+                        / (2.0 * epsilon);
+                    objective_at(original);
+                }
+
+                let norm = |values: &ArrayD<f32>| values.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let error = norm(&(&gradient - &numerical))
+                    / norm(&gradient).max(norm(&numerical)).max(1e-6);
+                max_error = max_error.max(error);
+            }
+
+            layers.push(max_error);
+        }
+
+        Ok(GradientCheck { layers })
+    }
+
+    /// The mean loss over `data` of the forward pass `compute_gradients` differentiates, plus
+    /// the weight penalties.
+    fn objective(
+        &self,
+        network: &NeuralNetwork,
+        data: &[DataPoint],
+        penalties: &[Option<Regularization>],
+    ) -> f32 {
+        let mut rng = self.chunk_rng(0);
+        let output = network.layers.iter().fold(
+            stack_rows(data.iter().map(|point| &point.inputs)),
+            |activations, layer| layer.forward_train(&activations, &mut rng).0,
+        );
+        let loss = self.loss.batch_value(
+            &output,
+            &stack_rows(data.iter().map(|point| &point.targets)),
+        );
+        let penalty: f32 = network
+            .parameters()
+            .iter()
+            .zip(penalties)
+            .filter_map(|(parameter, regularization)| {
+                regularization.map(|regularization| regularization.penalty(parameter))
+            })
+            .sum();
+        loss / data.len() as f32 + penalty
+    }
+}
@@ -0,0 +1,590 @@
+use crate::dense::{quantize_weights, smallest_weight_scale};
+use crate::matmul::{matmul, matmul_add};
+use crate::nd::{
+    concatenate, s, Array1, Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMutD, Axis,
+};
+use crate::{
+    matrix_grad, sigmoid, ActivationFunction, ActivationRange, Initializer, Layer, LayerCache,
+    NetworkLayer, NeuralNetwork, QuantizationConfig, QuantizationParams, QuantizedDense,
+    QuantizedLayer, QuantizedNeuralNetwork, Requantization,
+};
+use rand::rngs::StdRng;
+use ray_shared::result::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Scale of the integer gate pre-activations: the gate LUTs cover `[-8, 8)` in 256 steps.
+const GATE_INPUT_SCALE: f32 = 1.0 / 16.0;
+
+/// Fractional bits of the gate LUT outputs.
+const GATE_BITS: u32 = 15;
+
+/// Quantization of the integer hidden state, which `tanh` keeps within `[-1, 1]`.
+const HIDDEN_PARAMS: QuantizationParams = QuantizationParams {
+    scale: 1.0 / 128.0,
+    zero_point: 0,
+};
+
+/// Gated recurrent unit running over a sequence. Like [`crate::Conv1d`] inputs, samples are
+/// `input_size` channels of `sequence_length` values, so the frame at time `t` is every
+/// channel's value at `t`.
+///
+/// Every step computes, with `*` element-wise:
+///
+/// ```text
+/// z = sigmoid(W_z x + b_z + U_z h + c_z)
+/// r = sigmoid(W_r x + b_r + U_r h + c_r)
+/// n = tanh(W_n x + b_n + r * (U_n h + c_n))
+/// h = (1 - z) * n + z * h
+/// ```
+///
+/// The rows of `input_weights`, `recurrent_weights` and both biases hold the `z`, `r` and `n`
+/// parts in that order.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Gru {
+    pub input_size: usize,
+    pub hidden_size: usize,
+    pub sequence_length: usize,
+    /// Output the hidden state of every step, stored like the input, instead of the last one.
+    pub return_sequences: bool,
+    pub input_weights: Array2<f32>,
+    pub recurrent_weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    pub recurrent_biases: Array1<f32>,
+}
+
+/// Gate values of one step for a batch of frames.
+struct GruStep {
+    z: Array2<f32>,
+    r: Array2<f32>,
+    n: Array2<f32>,
+    /// `U_n h + c_n`, which `r` scales.
+    recurrent_n: Array2<f32>,
+}
+
+impl Gru {
+    pub fn new(
+        input_size: usize,
+        hidden_size: usize,
+        sequence_length: usize,
+        rng: &mut StdRng,
+    ) -> Self {
+        Self::with_initializer(
+            input_size,
+            hidden_size,
+            sequence_length,
+            &Initializer::Auto,
+            rng,
+        )
+    }
+
+    /// The recurrent weights of every gate are drawn as a square matrix of their own, so
+    /// [`Initializer::Orthogonal`] makes each of them orthogonal.
+    pub fn with_initializer(
+        input_size: usize,
+        hidden_size: usize,
+        sequence_length: usize,
+        initializer: &Initializer,
+        rng: &mut StdRng,
+    ) -> Self {
+        assert!(sequence_length > 0, "Sequences must not be empty.");
+        let gates = 3 * hidden_size;
+        let input_weights = initializer.weights(
+            (gates, input_size),
+            input_size,
+            hidden_size,
+            ActivationFunction::Sigmoid,
+            rng,
+        );
+        let gate_weights: Vec<Array2<f32>> = (0..3)
+            .map(|_| {
+                initializer.weights(
+                    (hidden_size, hidden_size),
+                    hidden_size,
+                    hidden_size,
+                    ActivationFunction::Sigmoid,
+                    rng,
+                )
+            })
+            .collect();
+        let gate_weights: Vec<ArrayView2<f32>> = gate_weights.iter().map(Array2::view).collect();
+
+        Gru {
+            input_size,
+            hidden_size,
+            sequence_length,
+            return_sequences: false,
+            input_weights,
+            recurrent_weights: concatenate(Axis(0), &gate_weights).unwrap(),
+            biases: Array1::zeros(gates),
+            recurrent_biases: Array1::zeros(gates),
+        }
+    }
+
+    /// The hidden state before the first frame.
+    pub fn initial_state(&self) -> Array1<f32> {
+        Array1::zeros(self.hidden_size)
+    }
+
+    /// Advances `hidden` by one frame of `input_size` values and returns the new state.
+    pub fn step(&self, frame: &Array1<f32>, hidden: &Array1<f32>) -> Array1<f32> {
+        let frames = frame.view().insert_axis(Axis(0));
+        let hidden = hidden.view().insert_axis(Axis(0));
+        let step = self.gates(frames, hidden);
+        next_state(&step, hidden).remove_axis(Axis(0))
+    }
+
+    /// The frames at time `t` of a batch.
+    fn frames<'a>(&self, input: ArrayView2<'a, f32>, t: usize) -> ArrayView2<'a, f32> {
+        input.slice_move(s![.., t..;self.sequence_length])
+    }
+
+    fn gates(&self, frames: ArrayView2<f32>, hidden: ArrayView2<f32>) -> GruStep {
+        let h = self.hidden_size;
+        let input = matmul(frames, self.input_weights.t()) + &self.biases;
+        let recurrent = matmul(hidden, self.recurrent_weights.t()) + &self.recurrent_biases;
+
+        let z = (&input.slice(s![.., ..h]) + &recurrent.slice(s![.., ..h])).mapv(sigmoid);
+        let r = (&input.slice(s![.., h..2 * h]) + &recurrent.slice(s![.., h..2 * h])).mapv(sigmoid);
+        let recurrent_n = recurrent.slice(s![.., 2 * h..]).to_owned();
+        let n = (&input.slice(s![.., 2 * h..]) + &(&r * &recurrent_n)).mapv(f32::tanh);
+
+        GruStep {
+            z,
+            r,
+            n,
+            recurrent_n,
+        }
+    }
+
+    /// Runs the whole sequence, calling `visit` with the previous state and the gates of every
+    /// step.
+    fn run(
+        &self,
+        input: ArrayView2<f32>,
+        mut visit: impl FnMut(&Array2<f32>, GruStep),
+    ) -> Array2<f32> {
+        let samples = input.nrows();
+        let length = self.sequence_length;
+        let mut hidden = Array2::zeros((samples, self.hidden_size));
+        let mut output = Array2::zeros((samples, self.output_size()));
+
+        for t in 0..length {
+            let step = self.gates(self.frames(input, t), hidden.view());
+            let next = next_state(&step, hidden.view());
+            if self.return_sequences {
+                output.slice_mut(s![.., t..;length]).assign(&next);
+            }
+            visit(&hidden, step);
+            hidden = next;
+        }
+
+        if self.return_sequences {
+            output
+        } else {
+            hidden
+        }
+    }
+}
+
+/// `(1 - z) * n + z * h`.
+fn next_state(step: &GruStep, hidden: ArrayView2<f32>) -> Array2<f32> {
+    &step.n + &(&step.z * &(&hidden - &step.n))
+}
+
+impl Layer for Gru {
+    fn input_size(&self) -> usize {
+        self.input_size * self.sequence_length
+    }
+
+    fn output_size(&self) -> usize {
+        if self.return_sequences {
+            self.hidden_size * self.sequence_length
+        } else {
+            self.hidden_size
+        }
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        self.run(input, |_, _| {})
+    }
+
+    /// Caches the previous state, `z`, `r`, `n` and `U_n h + c_n` of every step, in that order.
+    fn forward_train(&self, input: &Array2<f32>, _rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        let mut cache = Vec::with_capacity(5 * self.sequence_length);
+        let output = self.run(input.view(), |hidden, step| {
+            cache.extend([hidden.clone(), step.z, step.r, step.n, step.recurrent_n]);
+        });
+        (output, cache)
+    }
+
+    /// Backpropagation through time, from the last step to the first.
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        let h = self.hidden_size;
+        let length = self.sequence_length;
+        let [input_weights_grad, recurrent_weights_grad, biases_grad, recurrent_biases_grad] =
+            parameter_grads
+        else {
+            unreachable!("four parameters")
+        };
+        let mut grad = input_grad.then(|| Array2::zeros(input.raw_dim()));
+
+        let samples = input.nrows();
+        let mut hidden_grad = if self.return_sequences {
+            Array2::zeros((samples, h))
+        } else {
+            output_grad.clone()
+        };
+
+        for t in (0..length).rev() {
+            if self.return_sequences {
+                hidden_grad += &output_grad.slice(s![.., t..;length]);
+            }
+            let [hidden, z, r, n, recurrent_n] = &cache[5 * t..5 * t + 5] else {
+                unreachable!("five cached values per step")
+            };
+
+            let n_grad = &hidden_grad * &(1.0 - z) * (1.0 - &(n * n));
+            let z_grad = &hidden_grad * &(hidden - n) * z * (1.0 - z);
+            let r_grad = &n_grad * recurrent_n * r * (1.0 - r);
+
+            let mut gate_grad = Array2::zeros((samples, 3 * h));
+            gate_grad.slice_mut(s![.., ..h]).assign(&z_grad);
+            gate_grad.slice_mut(s![.., h..2 * h]).assign(&r_grad);
+            let mut recurrent_grad = gate_grad.clone();
+            gate_grad.slice_mut(s![.., 2 * h..]).assign(&n_grad);
+            recurrent_grad
+                .slice_mut(s![.., 2 * h..])
+                .assign(&(&n_grad * r));
+
+            let frames = self.frames(input.view(), t);
+            matmul_add(gate_grad.t(), frames, matrix_grad(input_weights_grad));
+            *biases_grad += &gate_grad.sum_axis(Axis(0));
+            matmul_add(
+                recurrent_grad.t(),
+                hidden.view(),
+                matrix_grad(recurrent_weights_grad),
+            );
+            *recurrent_biases_grad += &recurrent_grad.sum_axis(Axis(0));
+
+            if let Some(grad) = &mut grad {
+                grad.slice_mut(s![.., t..;length])
+                    .assign(&matmul(gate_grad.view(), self.input_weights.view()));
+            }
+            hidden_grad =
+                &hidden_grad * z + matmul(recurrent_grad.view(), self.recurrent_weights.view());
+        }
+
+        grad
+    }
+
+    fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        vec![
+            self.input_weights.view().into_dyn(),
+            self.recurrent_weights.view().into_dyn(),
+            self.biases.view().into_dyn(),
+            self.recurrent_biases.view().into_dyn(),
+        ]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        vec![
+            self.input_weights.view_mut().into_dyn(),
+            self.recurrent_weights.view_mut().into_dyn(),
+            self.biases.view_mut().into_dyn(),
+            self.recurrent_biases.view_mut().into_dyn(),
+        ]
+    }
+
+    fn penalized_parameters(&self) -> Vec<usize> {
+        vec![0, 1]
+    }
+
+    /// The hidden state has a known range, so the calibrated `output` is not needed.
+    fn quantize(
+        &self,
+        input: QuantizationParams,
+        _output: ActivationRange,
+        config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        Some(QuantizedLayer::Gru(Box::new(QuantizedGru {
+            input_size: self.input_size,
+            hidden_size: self.hidden_size,
+            sequence_length: self.sequence_length,
+            return_sequences: self.return_sequences,
+            input_kernel: quantize_gate_kernel(&self.input_weights, &self.biases, input, config),
+            recurrent_kernel: quantize_gate_kernel(
+                &self.recurrent_weights,
+                &self.recurrent_biases,
+                HIDDEN_PARAMS,
+                config,
+            ),
+        })))
+    }
+
+    fn smallest_weight_scale(&self, config: &QuantizationConfig) -> Option<f32> {
+        Some(
+            smallest_weight_scale(&self.input_weights, config)
+                .min(smallest_weight_scale(&self.recurrent_weights, config)),
+        )
+    }
+}
+
+/// Integer `weights * x + biases`. Only the accumulator and [`QuantizedDense::logits`] are
+/// used, so the requantization targets the gate LUT input instead of an output range.
+fn quantize_gate_kernel(
+    weights: &Array2<f32>,
+    biases: &Array1<f32>,
+    input: QuantizationParams,
+    config: &QuantizationConfig,
+) -> QuantizedDense {
+    let mut kernel = quantize_weights(
+        weights,
+        biases,
+        ActivationFunction::Linear,
+        input,
+        ActivationRange::default(),
+        config,
+    );
+    kernel.output = QuantizationParams {
+        scale: GATE_INPUT_SCALE,
+        zero_point: 0,
+    };
+    kernel.requantizations = kernel
+        .bias_scales
+        .iter()
+        .map(|&bias_scale| Requantization::from_scale(bias_scale / GATE_INPUT_SCALE))
+        .collect();
+    kernel
+}
+
+/// `f(x * GATE_INPUT_SCALE)` with [`GATE_BITS`] fractional bits for every LUT index.
+fn gate_table(f: fn(f32) -> f32) -> Vec<i32> {
+    (-128..128)
+        .map(|x| (f(x as f32 * GATE_INPUT_SCALE) * (1 << GATE_BITS) as f32).round() as i32)
+        .collect()
+}
+
+fn sigmoid_gate_table() -> &'static [i32] {
+    static TABLE: OnceLock<Vec<i32>> = OnceLock::new();
+    TABLE.get_or_init(|| gate_table(sigmoid))
+}
+
+fn tanh_gate_table() -> &'static [i32] {
+    static TABLE: OnceLock<Vec<i32>> = OnceLock::new();
+    TABLE.get_or_init(|| gate_table(f32::tanh))
+}
+
+fn lookup(table: &[i32], x: i32) -> i32 {
+    table[(x.clamp(-128, 127) + 128) as usize]
+}
+
+/// `a * b` of two values with [`GATE_BITS`] fractional bits, rounded half up.
+fn multiply_fixed(a: i32, b: i32) -> i32 {
+    ((a as i64 * b as i64 + (1 << (GATE_BITS - 1))) >> GATE_BITS) as i32
+}
+
+/// Integer counterpart of [`Gru`]. Gate pre-activations are requantized onto a shared grid and
+/// looked up in sigmoid and tanh LUTs with 15 fractional bits. The state update runs at that
+/// precision, and the hidden state is stored as int8 with scale `1/128`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QuantizedGru {
+    pub input_size: usize,
+    pub hidden_size: usize,
+    pub sequence_length: usize,
+    pub return_sequences: bool,
+    /// `W x + b` for all three gates.
+    pub input_kernel: QuantizedDense,
+    /// `U h + c` for all three gates.
+    pub recurrent_kernel: QuantizedDense,
+}
+
+impl QuantizedGru {
+    pub fn initial_state(&self) -> Array1<i8> {
+        Array1::from_elem(self.hidden_size, HIDDEN_PARAMS.zero_point as i8)
+    }
+
+    /// Advances `hidden` by one frame quantized like the layer input.
+    pub fn step(&self, frame: &Array1<i8>, hidden: &Array1<i8>) -> Array1<i8> {
+        let h = self.hidden_size;
+        let input = self
+            .input_kernel
+            .logits(&self.input_kernel.accumulate(frame));
+        let recurrent = self
+            .recurrent_kernel
+            .logits(&self.recurrent_kernel.accumulate(hidden));
+        let (sigmoid, tanh) = (sigmoid_gate_table(), tanh_gate_table());
+
+        Array1::from_shape_fn(h, |j| {
+            let z = lookup(sigmoid, input[j] + recurrent[j]);
+            let r = lookup(sigmoid, input[h + j] + recurrent[h + j]);
+            let n = lookup(
+                tanh,
+                input[2 * h + j] + multiply_fixed(r, recurrent[2 * h + j]),
+            );
+
+            // The state has 7 fractional bits, the gates 15.
+            let shift = GATE_BITS - 7;
+            let previous = (hidden[j] as i32) << shift;
+            let next = n + multiply_fixed(z, previous - n);
+            ((next + (1 << (shift - 1))) >> shift).clamp(-128, 127) as i8
+        })
+    }
+
+    pub fn feedforward(&self, input: &Array1<i8>) -> Array1<i8> {
+        let length = self.sequence_length;
+        let mut hidden = self.initial_state();
+        let mut output = Array1::zeros(self.hidden_size * length);
+
+        for t in 0..length {
+            let frame = input.slice(s![t..;length]).to_owned();
+            hidden = self.step(&frame, &hidden);
+            if self.return_sequences {
+                output.slice_mut(s![t..;length]).assign(&hidden);
+            }
+        }
+
+        if self.return_sequences {
+            output
+        } else {
+            hidden
+        }
+    }
+
+    pub fn output(&self) -> QuantizationParams {
+        HIDDEN_PARAMS
+    }
+}
+
+/// Number of GRU layers a network starts with, which [`GruStream`] steps through one frame at a
+/// time. `return_sequences` gives the flag of GRU layers and `None` for other layers; all but
+/// the last leading GRU have to return sequences, the last must not.
+fn leading_grus<T>(layers: &[T], return_sequences: impl Fn(&T) -> Option<bool>) -> Result<usize> {
+    let count = layers
+        .iter()
+        .take_while(|layer| return_sequences(layer).is_some())
+        .count();
+    if count == 0 {
+        bail!("Streaming needs a network that starts with a GRU layer.");
+    }
+    for (layer_idx, layer) in layers[..count].iter().enumerate() {
+        if return_sequences(layer) != Some(layer_idx + 1 < count) {
+            bail!(
+                "Layer {} cannot be streamed: every leading GRU layer but the last has to return sequences, the last one only its final state.",
+                layer_idx
+            );
+        }
+    }
+    Ok(count)
+}
+
+/// Runs a network one frame at a time, keeping the hidden state of its leading GRU layers
+/// between calls. The layers after them see the latest state on every frame.
+pub struct GruStream<'a> {
+    network: &'a NeuralNetwork,
+    /// Hidden state of every leading GRU layer.
+    pub states: Vec<Array1<f32>>,
+}
+
+impl<'a> GruStream<'a> {
+    pub fn new(network: &'a NeuralNetwork) -> Result<Self> {
+        let count = leading_grus(&network.layers, |layer| match layer {
+            NetworkLayer::Gru(gru) => Some(gru.return_sequences),
+            _ => None,
+        })?;
+        Ok(GruStream {
+            network,
+            states: Self::initial_states(&network.layers[..count]),
+        })
+    }
+
+    fn initial_states(layers: &[NetworkLayer]) -> Vec<Array1<f32>> {
+        layers
+            .iter()
+            .filter_map(|layer| match layer {
+                NetworkLayer::Gru(gru) => Some(gru.initial_state()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Starts over from the initial states.
+    pub fn reset(&mut self) {
+        self.states = Self::initial_states(&self.network.layers[..self.states.len()]);
+    }
+
+    /// Feeds one frame of `input_size` values and returns the network output.
+    pub fn push(&mut self, frame: &Array1<f32>) -> Array1<f32> {
+        let mut activations = frame.clone();
+        for (state, layer) in self.states.iter_mut().zip(&self.network.layers) {
+            if let NetworkLayer::Gru(gru) = layer {
+                *state = gru.step(&activations, state);
+                activations = state.clone();
+            }
+        }
+
+        self.network.layers[self.states.len()..]
+            .iter()
+            .fold(activations.insert_axis(Axis(0)), |activations, layer| {
+                layer.forward(activations.view())
+            })
+            .remove_axis(Axis(0))
+    }
+}
+
+/// Integer counterpart of [`GruStream`].
+pub struct QuantizedGruStream<'a> {
+    network: &'a QuantizedNeuralNetwork,
+    pub states: Vec<Array1<i8>>,
+}
+
+impl<'a> QuantizedGruStream<'a> {
+    pub fn new(network: &'a QuantizedNeuralNetwork) -> Result<Self> {
+        let count = leading_grus(&network.layers, |layer| match layer {
+            QuantizedLayer::Gru(gru) => Some(gru.return_sequences),
+            _ => None,
+        })?;
+        Ok(QuantizedGruStream {
+            network,
+            states: Self::initial_states(&network.layers[..count]),
+        })
+    }
+
+    fn initial_states(layers: &[QuantizedLayer]) -> Vec<Array1<i8>> {
+        layers
+            .iter()
+            .filter_map(|layer| match layer {
+                QuantizedLayer::Gru(gru) => Some(gru.initial_state()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn reset(&mut self) {
+        self.states = Self::initial_states(&self.network.layers[..self.states.len()]);
+    }
+
+    /// Feeds one frame quantized with [`QuantizedNeuralNetwork::input_params`].
+    pub fn push(&mut self, frame: &Array1<i8>) -> Array1<i8> {
+        let mut activations = frame.clone();
+        for (state, layer) in self.states.iter_mut().zip(&self.network.layers) {
+            if let QuantizedLayer::Gru(gru) = layer {
+                *state = gru.step(&activations, state);
+                activations = state.clone();
+            }
+        }
+
+        self.network.layers[self.states.len()..]
+            .iter()
+            .fold(activations, |activations, layer| {
+                layer.feedforward(&activations)
+            })
+    }
+}
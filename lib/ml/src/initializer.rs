@@ -0,0 +1,170 @@
+use crate::dense::random_weights;
+use crate::nd::Array2;
+use crate::{ActivationFunction, Dense, Layer, NeuralNetwork};
+use rand::distributions::Uniform;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Normal};
+use ray_shared::result::Result;
+use std::path::PathBuf;
+
+/// How [`NeuralNetwork::with_initializer`] fills the weights of new layers. Biases always
+/// start at zero.
+#[derive(Clone, Debug, Default)]
+pub enum Initializer {
+    /// He normal for the ReLU family, Glorot normal otherwise.
+    #[default]
+    Auto,
+    /// Uniform in `±sqrt(6 / (fan_in + fan_out))`.
+    GlorotUniform,
+    /// Normal with standard deviation `sqrt(2 / (fan_in + fan_out))`.
+    GlorotNormal,
+    /// Uniform in `±sqrt(6 / fan_in)`.
+    HeUniform,
+    /// Normal with standard deviation `sqrt(2 / fan_in)`.
+    HeNormal,
+    /// Uniform in `±sqrt(3 / fan_in)`.
+    LeCunUniform,
+    /// Normal with standard deviation `sqrt(1 / fan_in)`.
+    LeCunNormal,
+    /// Orthonormal rows, or columns for tall matrices, scaled by the given gain.
+    Orthogonal(f32),
+    /// Every weight set to the given value.
+    Constant(f32),
+    /// Copies the parameters of every layer of this network whose index and parameter shapes
+    /// match, for transfer learning. Other layers get [`Initializer::Auto`]. Layer
+    /// constructors only draw the latter; [`NeuralNetwork::transfer_parameters`] copies into
+    /// networks assembled from them.
+    Pretrained(Box<NeuralNetwork>),
+}
+
+impl Initializer {
+    /// [`Initializer::Pretrained`] with the network saved at `path`.
+    pub fn from_file(path: &PathBuf) -> Result<Self> {
+        Ok(Initializer::Pretrained(Box::new(
+            NeuralNetwork::load_from_file(path)?,
+        )))
+    }
+
+    /// Weights of `shape` for a layer with the given fans and activation.
+    pub(crate) fn weights(
+        &self,
+        shape: (usize, usize),
+        fan_in: usize,
+        fan_out: usize,
+        activation: ActivationFunction,
+        rng: &mut StdRng,
+    ) -> Array2<f32> {
+        let fans = (fan_in + fan_out) as f32;
+        match self {
+            Initializer::Auto | Initializer::Pretrained(_) => {
+                random_weights(shape, fan_in, fan_out, activation, rng)
+            }
+            Initializer::GlorotUniform => uniform(shape, (6.0 / fans).sqrt(), rng),
+            Initializer::GlorotNormal => normal(shape, (2.0 / fans).sqrt(), rng),
+            Initializer::HeUniform => uniform(shape, (6.0 / fan_in as f32).sqrt(), rng),
+            Initializer::HeNormal => normal(shape, (2.0 / fan_in as f32).sqrt(), rng),
+            Initializer::LeCunUniform => uniform(shape, (3.0 / fan_in as f32).sqrt(), rng),
+            Initializer::LeCunNormal => normal(shape, (1.0 / fan_in as f32).sqrt(), rng),
+            Initializer::Orthogonal(gain) => orthogonal(shape, rng) * *gain,
+            Initializer::Constant(value) => Array2::from_elem(shape, *value),
+        }
+    }
+}
+
+fn uniform(shape: (usize, usize), limit: f32, rng: &mut StdRng) -> Array2<f32> {
+    let uniform = Uniform::new_inclusive(-limit, limit);
+    Array2::from_shape_fn(shape, |_| uniform.sample(rng))
+}
+
+fn normal(shape: (usize, usize), std_dev: f32, rng: &mut StdRng) -> Array2<f32> {
+    let normal = Normal::new(0.0, std_dev).unwrap();
+    Array2::from_shape_fn(shape, |_| normal.sample(rng))
+}
+
+/// A Gaussian matrix orthonormalized with modified Gram-Schmidt along its shorter side.
+fn orthogonal(shape: (usize, usize), rng: &mut StdRng) -> Array2<f32> {
+    let (rows, columns) = shape;
+    let transposed = rows > columns;
+    let (count, length) = if transposed {
+        (columns, rows)
+    } else {
+        (rows, columns)
+    };
+
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    let mut vectors = Array2::<f64>::from_shape_fn((count, length), |_| normal.sample(rng));
+    for i in 0..count {
+        for j in 0..i {
+            let projection = vectors.row(i).dot(&vectors.row(j));
+            let previous = vectors.row(j).to_owned();
+            vectors.row_mut(i).scaled_add(-projection, &previous);
+        }
+        let norm = vectors.row(i).dot(&vectors.row(i)).sqrt();
+        vectors.row_mut(i).mapv_inplace(|x| x / norm);
+    }
+
+    let vectors = vectors.mapv(|x| x as f32);
+    if transposed {
+        vectors.reversed_axes()
+    } else {
+        vectors
+    }
+}
+
+impl NeuralNetwork {
+    /// [`NeuralNetwork::new`] with the weights drawn by `initializer`.
+    pub fn with_initializer(
+        layer_sizes: &[usize],
+        activations: &[ActivationFunction],
+        initializer: &Initializer,
+        rng: &mut StdRng,
+    ) -> Self {
+        assert!(
+            layer_sizes.len() >= 2,
+            "Network must have at least input and output layers."
+        );
+        assert_eq!(
+            layer_sizes.len() - 1,
+            activations.len(),
+            "Number of activations must be one less than number of layers."
+        );
+
+        let mut network = NeuralNetwork {
+            layers: layer_sizes
+                .windows(2)
+                .zip(activations.iter())
+                .map(|(sizes, &activation)| {
+                    Dense::with_initializer(sizes[0], sizes[1], activation, initializer, rng).into()
+                })
+                .collect(),
+        };
+
+        if let Initializer::Pretrained(source) = initializer {
+            network.transfer_parameters(source);
+        }
+
+        network
+    }
+
+    /// Copies the parameters of every layer of `source` into the layer at the same index if
+    /// it has the same parameter shapes, and returns how many layers got parameters.
+    pub fn transfer_parameters(&mut self, source: &NeuralNetwork) -> usize {
+        let mut copied = 0;
+        for (layer, source) in self.layers.iter_mut().zip(&source.layers) {
+            let parameters = source.parameters();
+            let matches = layer.parameters().len() == parameters.len()
+                && layer
+                    .parameters()
+                    .iter()
+                    .zip(&parameters)
+                    .all(|(a, b)| a.shape() == b.shape());
+            if matches && !parameters.is_empty() {
+                for (target, source) in layer.parameters_mut().iter_mut().zip(&parameters) {
+                    target.assign(source);
+                }
+                copied += 1;
+            }
+        }
+        copied
+    }
+}
@@ -0,0 +1,311 @@
+use crate::nd::{Array1, Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMut2, ArrayViewMutD};
+use crate::{
+    argmax, ActivationFunction, ActivationRange, BatchNorm, Conv1d, Dense, Dropout, Gru, Pool1d,
+    QuantizationConfig, QuantizationParams, QuantizedConv1d, QuantizedDense, QuantizedGru,
+    QuantizedPool1d, Reshape,
+};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+/// Intermediate values `forward_train` keeps for `backward`, such as pre-activations.
+pub type LayerCache = Vec<Array2<f32>>;
+
+/// `grad`, the gradient accumulator of a matrix parameter, as a matrix.
+pub(crate) fn matrix_grad(grad: &mut ArrayD<f32>) -> ArrayViewMut2<'_, f32> {
+    grad.view_mut()
+        .into_dimensionality()
+        .expect("gradient of a matrix parameter")
+}
+
+/// One stage of a [`crate::NeuralNetwork`]. Batches hold one sample per row; layers with
+/// spatial structure read a row as their input tensor flattened in row-major order.
+pub trait Layer {
+    fn input_size(&self) -> usize;
+
+    fn output_size(&self) -> usize;
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32>;
+
+    /// Forward pass during training, also returning what [`Layer::backward`] needs. `rng`
+    /// drives layers that behave randomly while training, such as [`crate::Dropout`].
+    fn forward_train(&self, input: &Array2<f32>, _rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        (self.forward(input.view()), Vec::new())
+    }
+
+    /// Backpropagates `output_grad`, the gradient with respect to this layer's output for
+    /// every sample. The gradients of the summed loss are added to `parameter_grads`, one
+    /// tensor per [`Layer::parameters`] entry, so callers can sum several batches in place.
+    /// Returns the gradient with respect to the input, which is skipped unless `input_grad`
+    /// is set; that saves the work for the first layer.
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>>;
+
+    /// The activation ending this layer and its input from `cache`. Losses fuse their
+    /// gradient with it on the output layer, which then continues with
+    /// [`Layer::backward_pre_activation`].
+    fn output_activation<'c>(
+        &self,
+        _cache: &'c LayerCache,
+    ) -> Option<(ActivationFunction, &'c Array2<f32>)> {
+        None
+    }
+
+    /// [`Layer::backward`] starting from the gradient with respect to the pre-activation
+    /// returned by [`Layer::output_activation`].
+    fn backward_pre_activation(
+        &self,
+        _input: &Array2<f32>,
+        _cache: &LayerCache,
+        _delta: &Array2<f32>,
+        _parameter_grads: &mut [ArrayD<f32>],
+        _input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        unreachable!("layer has no output activation")
+    }
+
+    /// Trainable tensors, in the order optimizers see them.
+    fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        Vec::new()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        Vec::new()
+    }
+
+    /// Indices into [`Layer::parameters`] of the weights that [`crate::Regularization`]
+    /// penalizes; biases are left out.
+    fn penalized_parameters(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// The integer counterpart, or `None` for layers that are the identity at inference.
+    /// `output` is the calibrated range of the layer's output.
+    fn quantize(
+        &self,
+        input: QuantizationParams,
+        output: ActivationRange,
+        config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer>;
+
+    /// Smallest weight scale `quantize` would pick before clamping; `None` without weights.
+    fn smallest_weight_scale(&self, _config: &QuantizationConfig) -> Option<f32> {
+        None
+    }
+}
+
+/// The layer types a network can be built from.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum NetworkLayer {
+    Dense(Dense),
+    Conv1d(Conv1d),
+    Pool1d(Pool1d),
+    Reshape(Reshape),
+    Dropout(Dropout),
+    BatchNorm(BatchNorm),
+    Gru(Gru),
+}
+
+impl NetworkLayer {
+    pub fn as_layer(&self) -> &dyn Layer {
+        match self {
+            NetworkLayer::Dense(layer) => layer,
+            NetworkLayer::Conv1d(layer) => layer,
+            NetworkLayer::Pool1d(layer) => layer,
+            NetworkLayer::Reshape(layer) => layer,
+            NetworkLayer::Dropout(layer) => layer,
+            NetworkLayer::BatchNorm(layer) => layer,
+            NetworkLayer::Gru(layer) => layer,
+        }
+    }
+
+    pub fn as_layer_mut(&mut self) -> &mut dyn Layer {
+        match self {
+            NetworkLayer::Dense(layer) => layer,
+            NetworkLayer::Conv1d(layer) => layer,
+            NetworkLayer::Pool1d(layer) => layer,
+            NetworkLayer::Reshape(layer) => layer,
+            NetworkLayer::Dropout(layer) => layer,
+            NetworkLayer::BatchNorm(layer) => layer,
+            NetworkLayer::Gru(layer) => layer,
+        }
+    }
+}
+
+impl From<Dense> for NetworkLayer {
+    fn from(layer: Dense) -> Self {
+        NetworkLayer::Dense(layer)
+    }
+}
+
+impl From<Conv1d> for NetworkLayer {
+    fn from(layer: Conv1d) -> Self {
+        NetworkLayer::Conv1d(layer)
+    }
+}
+
+impl From<Pool1d> for NetworkLayer {
+    fn from(layer: Pool1d) -> Self {
+        NetworkLayer::Pool1d(layer)
+    }
+}
+
+impl From<Reshape> for NetworkLayer {
+    fn from(layer: Reshape) -> Self {
+        NetworkLayer::Reshape(layer)
+    }
+}
+
+impl From<Dropout> for NetworkLayer {
+    fn from(layer: Dropout) -> Self {
+        NetworkLayer::Dropout(layer)
+    }
+}
+
+impl From<BatchNorm> for NetworkLayer {
+    fn from(layer: BatchNorm) -> Self {
+        NetworkLayer::BatchNorm(layer)
+    }
+}
+
+impl From<Gru> for NetworkLayer {
+    fn from(layer: Gru) -> Self {
+        NetworkLayer::Gru(layer)
+    }
+}
+
+impl Layer for NetworkLayer {
+    fn input_size(&self) -> usize {
+        self.as_layer().input_size()
+    }
+
+    fn output_size(&self) -> usize {
+        self.as_layer().output_size()
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        self.as_layer().forward(input)
+    }
+
+    fn forward_train(&self, input: &Array2<f32>, rng: &mut StdRng) -> (Array2<f32>, LayerCache) {
+        self.as_layer().forward_train(input, rng)
+    }
+
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        self.as_layer()
+            .backward(input, cache, output_grad, parameter_grads, input_grad)
+    }
+
+    fn output_activation<'c>(
+        &self,
+        cache: &'c LayerCache,
+    ) -> Option<(ActivationFunction, &'c Array2<f32>)> {
+        self.as_layer().output_activation(cache)
+    }
+
+    fn backward_pre_activation(
+        &self,
+        input: &Array2<f32>,
+        cache: &LayerCache,
+        delta: &Array2<f32>,
+        parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        self.as_layer()
+            .backward_pre_activation(input, cache, delta, parameter_grads, input_grad)
+    }
+
+    fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        self.as_layer().parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        self.as_layer_mut().parameters_mut()
+    }
+
+    fn penalized_parameters(&self) -> Vec<usize> {
+        self.as_layer().penalized_parameters()
+    }
+
+    fn quantize(
+        &self,
+        input: QuantizationParams,
+        output: ActivationRange,
+        config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        self.as_layer().quantize(input, output, config)
+    }
+
+    fn smallest_weight_scale(&self, config: &QuantizationConfig) -> Option<f32> {
+        self.as_layer().smallest_weight_scale(config)
+    }
+}
+
+/// Integer layers of a [`crate::QuantizedNeuralNetwork`], one kind per quantizable
+/// [`NetworkLayer`].
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum QuantizedLayer {
+    Dense(QuantizedDense),
+    Conv1d(QuantizedConv1d),
+    Pool1d(QuantizedPool1d),
+    /// Boxed, as the two kernels would make every other variant twice as large.
+    Gru(Box<QuantizedGru>),
+}
+
+impl QuantizedLayer {
+    pub fn feedforward(&self, input: &Array1<i8>) -> Array1<i8> {
+        match self {
+            QuantizedLayer::Dense(layer) => layer.feedforward(input),
+            QuantizedLayer::Conv1d(layer) => layer.feedforward(input),
+            QuantizedLayer::Pool1d(layer) => layer.feedforward(input),
+            QuantizedLayer::Gru(layer) => layer.feedforward(input),
+        }
+    }
+
+    /// Index of the largest output; see [`QuantizedDense::argmax`].
+    pub fn argmax(&self, input: &Array1<i8>) -> usize {
+        match self {
+            QuantizedLayer::Dense(layer) => layer.argmax(input),
+            QuantizedLayer::Conv1d(layer) => argmax(layer.feedforward(input).iter()),
+            QuantizedLayer::Pool1d(layer) => argmax(layer.feedforward(input).iter()),
+            QuantizedLayer::Gru(layer) => argmax(layer.feedforward(input).iter()),
+        }
+    }
+
+    pub fn input(&self) -> QuantizationParams {
+        match self {
+            QuantizedLayer::Dense(layer) => layer.input,
+            QuantizedLayer::Conv1d(layer) => layer.kernel.input,
+            QuantizedLayer::Pool1d(layer) => layer.params,
+            QuantizedLayer::Gru(layer) => layer.input_kernel.input,
+        }
+    }
+
+    pub fn output(&self) -> QuantizationParams {
+        match self {
+            QuantizedLayer::Dense(layer) => layer.output,
+            QuantizedLayer::Conv1d(layer) => layer.kernel.output,
+            QuantizedLayer::Pool1d(layer) => layer.params,
+            QuantizedLayer::Gru(layer) => layer.output(),
+        }
+    }
+
+    pub fn as_dense(&self) -> Option<&QuantizedDense> {
+        match self {
+            QuantizedLayer::Dense(layer) => Some(layer),
+            _ => None,
+        }
+    }
+}
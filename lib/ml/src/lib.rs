@@ -1,194 +1,806 @@
 pub use ndarray as nd;
 
-use nd::{Array1, Array2, Axis};
+use matmul::matmul_add;
+use nd::{Array1, Array2, ArrayD, ArrayView2, ArrayViewD, ArrayViewMut1, ArrayViewMutD, Axis, Zip};
 use rand::rngs::StdRng;
-use rand_distr::{Distribution, Normal};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use ray_shared::result::{bail, Result};
-use rayon::prelude::*;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+mod batch_norm;
+mod calibration;
+mod callback;
+mod checkpoint;
+mod conv;
+mod dense;
+mod dropout;
+mod gradient_check;
+mod gru;
+mod initializer;
+mod layer;
+mod loss;
+mod matmul;
+mod optimizer;
+mod pool;
+mod reduce;
+mod regularization;
+mod reshape;
+mod schedule;
+mod sigmoid;
+mod validation;
+
+pub use batch_norm::*;
+pub use calibration::*;
+pub use callback::*;
+pub use checkpoint::*;
+pub use conv::*;
+pub use dense::*;
+pub use dropout::*;
+pub use gradient_check::*;
+pub use gru::*;
+pub use initializer::*;
+pub use layer::*;
+pub use loss::*;
+pub use optimizer::*;
+pub use pool::*;
+pub use reduce::*;
+pub use regularization::*;
+pub use reshape::*;
+pub use schedule::*;
+pub use sigmoid::*;
+pub use validation::*;
 
 #[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
 pub enum ActivationFunction {
     ReLU,
     Sigmoid,
     Linear,
+    /// Normalizes the whole layer into a probability distribution.
+    Softmax,
+    Tanh,
+    /// ReLU passing negative inputs scaled by the given slope.
+    LeakyReLU(f32),
+    /// ReLU capped at the given maximum, see [`ActivationFunction::RELU6`].
+    ClippedReLU(f32),
+    /// `relu6(x + 3) / 6`, a piecewise-linear sigmoid.
+    HardSigmoid,
+    /// `x * relu6(x + 3) / 6`.
+    HardSwish,
+    /// `x * sigmoid(x)`, also known as swish.
+    SiLU,
+    /// Gaussian error linear unit, in its tanh approximation.
+    GELU,
+}
+
+/// `sqrt(2 / pi)` and the cubic coefficient of the tanh approximation of GELU.
+const GELU_SCALE: f32 = 0.797_884_6;
+const GELU_CUBIC: f32 = 0.044_715;
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
 }
 
 impl ActivationFunction {
+    pub const RELU6: Self = ActivationFunction::ClippedReLU(6.0);
+
+    /// Element-wise activations; softmax is handled on whole rows by the callers.
+    fn apply(&self, x: f32) -> f32 {
+        match *self {
+            ActivationFunction::Sigmoid => sigmoid(x),
+            ActivationFunction::ReLU => x.max(0.0),
+            ActivationFunction::Linear => x,
+            ActivationFunction::Softmax => unreachable!("softmax is not element-wise"),
+            ActivationFunction::Tanh => x.tanh(),
+            ActivationFunction::LeakyReLU(slope) => {
+                if x > 0.0 {
+                    x
+                } else {
+                    slope * x
+                }
+            }
+            ActivationFunction::ClippedReLU(max) => x.max(0.0).min(max),
+            ActivationFunction::HardSigmoid => ((x + 3.0) / 6.0).clamp(0.0, 1.0),
+            ActivationFunction::HardSwish => x * ((x + 3.0) / 6.0).clamp(0.0, 1.0),
+            ActivationFunction::SiLU => x * sigmoid(x),
+            ActivationFunction::GELU => {
+                0.5 * x * (1.0 + (GELU_SCALE * (x + GELU_CUBIC * x * x * x)).tanh())
+            }
+        }
+    }
+
+    fn slope(&self, x: f32) -> f32 {
+        match *self {
+            ActivationFunction::Sigmoid => {
+                let a = sigmoid(x);
+                a * (1.0 - a)
+            }
+            ActivationFunction::ReLU => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ActivationFunction::Linear => 1.0,
+            ActivationFunction::Softmax => unreachable!("softmax is not element-wise"),
+            ActivationFunction::Tanh => 1.0 - x.tanh().powi(2),
+            ActivationFunction::LeakyReLU(slope) => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    slope
+                }
+            }
+            ActivationFunction::ClippedReLU(max) => {
+                if x > 0.0 && x < max {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ActivationFunction::HardSigmoid => {
+                if x > -3.0 && x < 3.0 {
+                    1.0 / 6.0
+                } else {
+                    0.0
+                }
+            }
+            ActivationFunction::HardSwish => {
+                if x <= -3.0 {
+                    0.0
+                } else if x >= 3.0 {
+                    1.0
+                } else {
+                    (2.0 * x + 3.0) / 6.0
+                }
+            }
+            ActivationFunction::SiLU => {
+                let a = sigmoid(x);
+                a * (1.0 + x * (1.0 - a))
+            }
+            ActivationFunction::GELU => {
+                let t = (GELU_SCALE * (x + GELU_CUBIC * x * x * x)).tanh();
+                let inner_slope = GELU_SCALE * (1.0 + 3.0 * GELU_CUBIC * x * x);
+                0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * inner_slope
+            }
+        }
+    }
+
+    /// Output range of the bounded activations, which need no calibration.
+    fn fixed_range(&self) -> Option<ActivationRange> {
+        match self {
+            ActivationFunction::Sigmoid
+            | ActivationFunction::Softmax
+            | ActivationFunction::HardSigmoid => Some(ActivationRange { min: 0.0, max: 1.0 }),
+            ActivationFunction::Tanh => Some(ActivationRange {
+                min: -1.0,
+                max: 1.0,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Largest pre-activation magnitude the integer LUT of this activation covers for outputs
+    /// in `output`. Past it the curve is flat or already beyond the output range.
+    fn table_bound(&self, output: ActivationRange) -> f32 {
+        match self {
+            ActivationFunction::Tanh => 4.0,
+            ActivationFunction::HardSigmoid => 3.0,
+            ActivationFunction::HardSwish => output.max.max(3.0),
+            ActivationFunction::SiLU => (output.max + 1.0).max(8.0),
+            ActivationFunction::GELU => (output.max + 1.0).max(4.0),
+            _ => unreachable!("{:?} is computed without a table", self),
+        }
+    }
+
+    /// The integer LUT: outputs quantized with `output` for the pre-activations `-128..128`
+    /// times `input_scale`.
+    fn lookup_table(&self, input_scale: f32, output: QuantizationParams) -> Vec<i8> {
+        (-128..128)
+            .map(|x| output.quantize(self.apply(x as f32 * input_scale)))
+            .collect()
+    }
+
     pub fn activate(&self, x: &Array1<f32>) -> Array1<f32> {
         match self {
-            ActivationFunction::Sigmoid => x.mapv(|x| 1.0 / (1.0 + (-x).exp())),
-            ActivationFunction::ReLU => x.mapv(|x| x.max(0.0)),
-            ActivationFunction::Linear => x.clone(),
+            ActivationFunction::Softmax => {
+                let mut a = x.clone();
+                softmax_in_place(a.view_mut());
+                a
+            }
+            _ => x.mapv(|x| self.apply(x)),
         }
     }
 
+    /// Element-wise derivative. For softmax this is only the diagonal of the Jacobian, use
+    /// [`ActivationFunction::backward`] to propagate gradients through it.
     pub fn derivative(&self, x: &Array1<f32>) -> Array1<f32> {
         match self {
-            ActivationFunction::Sigmoid => {
-                let a = x.mapv(|x| 1.0 / (1.0 + (-x).exp()));
+            ActivationFunction::Softmax => {
+                let a = self.activate(x);
                 &a * &(1.0 - &a)
             }
-            ActivationFunction::ReLU => x.mapv(|x| if x > 0.0 { 1.0 } else { 0.0 }),
-            ActivationFunction::Linear => Array1::ones(x.len()),
+            _ => x.mapv(|x| self.slope(x)),
         }
     }
-}
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct Layer {
-    pub weights: Array2<f32>,
-    pub biases: Array1<f32>,
-    pub activation: ActivationFunction,
-}
+    /// Maps the gradient with respect to the activation output onto the pre-activation `x`.
+    pub fn backward(&self, x: &Array1<f32>, grad: &Array1<f32>) -> Array1<f32> {
+        match self {
+            ActivationFunction::Softmax => {
+                let a = self.activate(x);
+                let dot = a.dot(grad);
+                &a * &(grad - dot)
+            }
+            _ => grad * &self.derivative(x),
+        }
+    }
 
-impl Layer {
-    pub fn new(
-        input_size: usize,
-        output_size: usize,
-        activation: ActivationFunction,
-        rng: &mut StdRng,
-    ) -> Self {
-        let std_dev = match activation {
-            ActivationFunction::Sigmoid | ActivationFunction::Linear => {
-                ((2.0) / (input_size + output_size) as f32).sqrt()
+    /// [`ActivationFunction::activate`] for a batch with one sample per row.
+    pub fn activate_batch(&self, x: &Array2<f32>) -> Array2<f32> {
+        match self {
+            ActivationFunction::Softmax => {
+                let mut a = x.clone();
+                for row in a.rows_mut() {
+                    softmax_in_place(row);
+                }
+                a
             }
-            ActivationFunction::ReLU => (2.0 / input_size as f32).sqrt(),
-        };
-        let normal = Normal::new(0.0, std_dev).unwrap();
-        let weights = Array2::from_shape_fn((output_size, input_size), |_| normal.sample(rng));
-        let biases = Array1::zeros(output_size);
+            _ => x.mapv(|x| self.apply(x)),
+        }
+    }
 
-        Layer {
-            weights,
-            biases,
-            activation,
+    /// [`ActivationFunction::backward`] for a batch with one sample per row.
+    pub fn backward_batch(&self, x: &Array2<f32>, grad: &Array2<f32>) -> Array2<f32> {
+        match self {
+            ActivationFunction::Softmax => {
+                let a = self.activate_batch(x);
+                let dots = (&a * grad).sum_axis(Axis(1)).insert_axis(Axis(1));
+                &a * &(grad - &dots)
+            }
+            _ => Zip::from(x)
+                .and(grad)
+                .map_collect(|&x, &grad| grad * self.slope(x)),
         }
     }
 }
 
+fn softmax_in_place(mut x: ArrayViewMut1<f32>) {
+    let max = x.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    x.mapv_inplace(|x| (x - max).exp());
+    let sum = x.sum();
+    x /= sum;
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct NeuralNetwork {
-    pub layers: Vec<Layer>,
+    pub layers: Vec<NetworkLayer>,
+}
+
+/// Start network files written since layers other than dense ones exist. Older files hold
+/// only dense layers and start with the layer count instead. The last byte is the version of
+/// the layout, bumped whenever a serialized layer changes.
+const NETWORK_FILE_MAGIC: [u8; 8] = *b"RAYNN\0\0\x02";
+/// Version 3 stores the sigmoid LUT layout of every [`QuantizedDense`].
+const QUANTIZED_NETWORK_FILE_MAGIC: [u8; 8] = *b"RAYQN\0\0\x03";
+
+fn save_with_magic<T: Serialize>(path: &PathBuf, magic: [u8; 8], value: &T) -> Result<()> {
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&magic)?;
+    bincode::serialize_into(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Opens a file written by [`save_with_magic`] and returns its first 8 bytes along with the
+/// reader positioned after them. Files of another version of the layout are rejected.
+fn open_with_magic(
+    path: &PathBuf,
+    magic: [u8; 8],
+    name: &str,
+) -> Result<([u8; 8], BufReader<File>)> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+
+    let mut start = [0; 8];
+    reader.read_exact(&mut start)?;
+    if start != magic && start[..7] == magic[..7] {
+        bail!(
+            "Unsupported {} version {} in {}, only version {} can be read.",
+            name,
+            start[7],
+            path.display(),
+            magic[7]
+        );
+    }
+
+    Ok((start, reader))
+}
+
+/// Reads a file written by [`save_with_magic`], or converts the `L` stored by a file from
+/// before the magic existed.
+fn load_with_magic<T, L>(
+    path: &PathBuf,
+    magic: [u8; 8],
+    name: &str,
+    legacy: impl FnOnce(L) -> T,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+    L: DeserializeOwned,
+{
+    let (start, reader) = open_with_magic(path, magic, name)?;
+    if start == magic {
+        return Ok(bincode::deserialize_from(reader)?);
+    }
+
+    Ok(legacy(bincode::deserialize_from(
+        (&start[..]).chain(reader),
+    )?))
+}
+
+/// Reads a file written by [`save_with_magic`] for a format that has no older layout.
+fn load_magic_only<T: DeserializeOwned>(path: &PathBuf, magic: [u8; 8], name: &str) -> Result<T> {
+    let (start, reader) = open_with_magic(path, magic, name)?;
+    if start != magic {
+        bail!("{} is not a {} file.", path.display(), name);
+    }
+
+    Ok(bincode::deserialize_from(reader)?)
 }
 
 impl NeuralNetwork {
+    /// Dense layers of the given sizes, initialized with [`Initializer::Auto`].
     pub fn new(
         layer_sizes: &[usize],
         activations: &[ActivationFunction],
         rng: &mut StdRng,
     ) -> Self {
-        assert!(
-            layer_sizes.len() >= 2,
-            "Network must have at least input and output layers."
-        );
-        assert_eq!(
-            layer_sizes.len() - 1,
-            activations.len(),
-            "Number of activations must be one less than number of layers."
-        );
+        Self::with_initializer(layer_sizes, activations, &Initializer::Auto, rng)
+    }
 
-        let layers = layer_sizes
-            .windows(2)
-            .zip(activations.iter())
-            .map(|(sizes, &activation)| Layer::new(sizes[0], sizes[1], activation, rng))
-            .collect();
+    pub fn feedforward(&self, input: &Array1<f32>) -> Array1<f32> {
+        self.feedforward_view(input.view().insert_axis(Axis(0)))
+            .remove_axis(Axis(0))
+    }
 
-        NeuralNetwork { layers }
+    /// Runs every row of `inputs` through the network, one batched pass per layer.
+    pub fn feedforward_batch(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        self.feedforward_view(inputs.view())
     }
 
-    pub fn feedforward(&self, input: &Array1<f32>) -> Array1<f32> {
-        let mut activations = input.clone();
+    /// The first layer reads `inputs` in place, so no copy is made of it.
+    fn feedforward_view(&self, inputs: ArrayView2<f32>) -> Array2<f32> {
+        let Some((first, rest)) = self.layers.split_first() else {
+            return inputs.to_owned();
+        };
+        rest.iter()
+            .fold(first.forward(inputs), |activations, layer| {
+                layer.forward(activations.view())
+            })
+    }
 
-        for layer in &self.layers {
-            let z = layer.weights.dot(&activations) + &layer.biases;
-            activations = layer.activation.activate(&z);
-        }
+    /// Trainable tensors of all layers, in the order optimizers see them.
+    pub fn parameters(&self) -> Vec<ArrayViewD<'_, f32>> {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.parameters())
+            .collect()
+    }
 
-        activations
+    pub fn parameters_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.parameters_mut())
+            .collect()
     }
 
     pub fn save_to_file(&self, path: &PathBuf) -> Result<()> {
-        let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, self)?;
-        Ok(())
+        save_with_magic(path, NETWORK_FILE_MAGIC, self)
     }
 
+    /// Also reads files written before other layer types existed, which hold dense layers
+    /// only.
     pub fn load_from_file(path: &PathBuf) -> Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        load_with_magic(path, NETWORK_FILE_MAGIC, "network", |layers: Vec<Dense>| {
+            NeuralNetwork {
+                layers: layers.into_iter().map(NetworkLayer::from).collect(),
+            }
+        })
+    }
+
+    /// Quantizes the network assuming every activation (including the input)
+    /// lies in `[-1, 1]`, which matches how the examples normalize their data. Nothing is
+    /// printed; [`NeuralNetwork::quantization_warnings`] lists the adjustments it makes.
+    pub fn quantize(&self) -> Result<QuantizedNeuralNetwork> {
+        self.quantize_calibrated(&Calibration::uniform(
+            self.layers.len(),
+            ActivationRange::default(),
+        ))
+    }
 
-        let net = bincode::deserialize_from(reader)?;
-        Ok(net)
+    /// Quantizes the network with activation scales taken from `calibration`, usually the
+    /// result of [`NeuralNetwork::calibrate`].
+    pub fn quantize_calibrated(&self, calibration: &Calibration) -> Result<QuantizedNeuralNetwork> {
+        self.quantize_with(calibration, &QuantizationConfig::default())
     }
 
-    pub fn quantize(&self) -> QuantizedNeuralNetwork {
+    /// Folds batch normalizations into the layers before them first, and fails if one cannot
+    /// be folded.
+    pub fn quantize_with(
+        &self,
+        calibration: &Calibration,
+        config: &QuantizationConfig,
+    ) -> Result<QuantizedNeuralNetwork> {
+        if calibration.ranges.len() != self.layers.len() + 1 {
+            bail!(
+                "Calibration holds {} ranges, expected one for the input and one per layer ({}).",
+                calibration.ranges.len(),
+                self.layers.len() + 1
+            );
+        }
+
+        if self
+            .layers
+            .iter()
+            .any(|layer| matches!(layer, NetworkLayer::BatchNorm(_)))
+        {
+            let (layers, folded) = self.folded_layers()?;
+            // A folded layer produces the normalized output, so its own range is dropped.
+            let mut calibration = calibration.clone();
+            for &layer_idx in folded.iter().rev() {
+                calibration.ranges.remove(layer_idx);
+            }
+            return NeuralNetwork { layers }.quantize_with(&calibration, config);
+        }
+
         let mut quant_layers = Vec::new();
+        let mut input = calibration.ranges[0].quantization_params(config.activation_scheme);
 
-        for (layer_idx, layer) in self.layers.iter().enumerate() {
-            // Compute weight scale based on maximum absolute weight
-            let max_weight = layer.weights.mapv(f32::abs).fold(0.0f32, |a, b| a.max(*b));
-
-            // Enforce a minimum weight_scale to prevent quantized weights from being all zeros
-            let min_weight_scale = 1e-6;
-            let weight_scale = if max_weight == 0.0 {
-                1.0
-            } else {
-                let scale = max_weight / 127.0;
-                if scale < min_weight_scale {
-                    println!(
-                        "Layer {}: weight_scale {:.6} is below min_threshold {:.6}. Setting to min_scale.",
-                        layer_idx, scale, min_weight_scale
-                    );
-                    min_weight_scale
-                } else {
-                    scale
-                }
-            };
+        for (layer, &output) in self.layers.iter().zip(&calibration.ranges[1..]) {
+            // Layers that vanish at inference hand their input grid on unchanged.
+            if let Some(quant_layer) = layer.quantize(input, output, config) {
+                input = quant_layer.output();
+                quant_layers.push(quant_layer);
+            }
+        }
 
-            let quant_weights = layer
-                .weights
-                .mapv(|weight| ((weight / weight_scale).round().clamp(-128.0, 127.0)) as i8);
+        Ok(QuantizedNeuralNetwork {
+            layers: quant_layers,
+        })
+    }
 
-            let bias_scale = weight_scale;
+    /// Adjustments [`NeuralNetwork::quantize_with`] makes to keep the network representable.
+    pub fn quantization_warnings(&self, config: &QuantizationConfig) -> Vec<QuantizationWarning> {
+        self.layers
+            .iter()
+            .enumerate()
+            .filter_map(|(layer_idx, layer)| {
+                let smallest_scale = layer.smallest_weight_scale(config)?;
+
+                (smallest_scale < MIN_WEIGHT_SCALE).then_some(
+                    QuantizationWarning::WeightScaleClamped {
+                        layer: layer_idx,
+                        scale: smallest_scale,
+                        min_scale: MIN_WEIGHT_SCALE,
+                    },
+                )
+            })
+            .collect()
+    }
+}
 
-            let quant_biases = layer.biases.mapv(|bias| {
-                let scaled_bias = bias / bias_scale;
-                if scaled_bias.is_finite() {
-                    scaled_bias.round() as i32
-                } else if bias > 0.0 {
-                    i32::MAX
-                } else {
-                    i32::MIN
-                }
-            });
+/// Smallest weight scale `quantize_with` uses.
+const MIN_WEIGHT_SCALE: f32 = 1e-6;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum QuantizationWarning {
+    /// Weights this small would round to zero, so the scale was raised to `min_scale`.
+    WeightScaleClamped {
+        layer: usize,
+        scale: f32,
+        min_scale: f32,
+    },
+}
 
-            quant_layers.push(QuantizedLayer {
-                weights: quant_weights,
-                biases: quant_biases,
-                activation: layer.activation,
-                weight_scale,
-                bias_scale,
-            });
+impl fmt::Display for QuantizationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuantizationWarning::WeightScaleClamped {
+                layer,
+                scale,
+                min_scale,
+            } => write!(
+                f,
+                "Layer {}: weight_scale {:.6} is below min_threshold {:.6}. Setting to min_scale.",
+                layer, scale, min_scale
+            ),
         }
+    }
+}
 
-        QuantizedNeuralNetwork {
-            layers: quant_layers,
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum WeightGranularity {
+    /// One scale shared by the whole weight matrix.
+    #[default]
+    PerTensor,
+    /// One scale per output neuron (weight matrix row).
+    PerChannel,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum QuantizationScheme {
+    /// Zero maps to zero and the range is mirrored around it.
+    #[default]
+    Symmetric,
+    /// The range is mapped onto all 256 levels through a zero-point, which suits
+    /// non-negative tensors such as normalized features and sigmoid/ReLU outputs.
+    Asymmetric,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub struct QuantizationConfig {
+    pub weight_granularity: WeightGranularity,
+    /// Scheme used for the input vector and every activation; weights stay symmetric.
+    pub activation_scheme: QuantizationScheme,
+    /// Table layout of sigmoid layers.
+    pub sigmoid_lut: SigmoidLut,
+}
+
+/// Affine mapping between a real value and an `i8`: `real = scale * (q - zero_point)`.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct QuantizationParams {
+    pub scale: f32,
+    pub zero_point: i32,
+}
+
+impl Default for QuantizationParams {
+    fn default() -> Self {
+        QuantizationParams {
+            scale: DEFAULT_ACTIVATION_SCALE,
+            zero_point: 0,
+        }
+    }
+}
+
+impl QuantizationParams {
+    pub fn quantize(&self, x: f32) -> i8 {
+        ((x / self.scale).round() + self.zero_point as f32).clamp(-128.0, 127.0) as i8
+    }
+
+    pub fn dequantize(&self, q: i8) -> f32 {
+        (q as i32 - self.zero_point) as f32 * self.scale
+    }
+}
+
+/// Scale assumed for activations when no better information is available: `[-1, 1]` maps to
+/// `[-127, 127]`.
+const DEFAULT_ACTIVATION_SCALE: f32 = 1.0 / 127.0;
+
+/// Scale of the logits fed to the integer softmax, one step of [`softmax_exp_table`].
+const SOFTMAX_INPUT_SCALE: f32 = 1.0 / 32.0;
+
+/// Fractional bits of the `exp` values produced by [`softmax_exp_table`].
+const SOFTMAX_EXP_BITS: u32 = 15;
+
+/// `exp(-d * SOFTMAX_INPUT_SCALE)` in Q15 for every distance `d` to the largest logit. Past the
+/// end the contribution is below `exp(-8)` and is treated as zero.
+fn softmax_exp_table() -> &'static [i32] {
+    static TABLE: OnceLock<Vec<i32>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        (0..256)
+            .map(|d| {
+                let x = -(d as f64) * SOFTMAX_INPUT_SCALE as f64;
+                (x.exp() * (1 << SOFTMAX_EXP_BITS) as f64).round() as i32
+            })
+            .collect()
+    })
+}
+
+/// Fixed-point representation of a real rescaling factor: `x * multiplier / 2^(31 + shift)`.
+///
+/// `multiplier` is normalized to `[2^30, 2^31)`, so the factor keeps 31 bits of precision and
+/// applying it only needs an integer multiply and an arithmetic shift.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Requantization {
+    pub multiplier: i32,
+    pub shift: i32,
+}
+
+impl Requantization {
+    pub fn from_scale(scale: f32) -> Self {
+        if !(scale.is_finite() && scale > 0.0) {
+            return Requantization {
+                multiplier: 0,
+                shift: 0,
+            };
+        }
+
+        let mut mantissa = scale as f64;
+        let mut shift = 0;
+        while mantissa < 0.5 {
+            mantissa *= 2.0;
+            shift += 1;
+        }
+        while mantissa >= 1.0 {
+            mantissa /= 2.0;
+            shift -= 1;
+        }
+
+        let mut multiplier = (mantissa * (1i64 << 31) as f64).round() as i64;
+        if multiplier == 1i64 << 31 {
+            multiplier /= 2;
+            shift -= 1;
         }
+
+        // Factors outside of what a 62 bit product can express saturate.
+        if shift > 31 {
+            Requantization {
+                multiplier: 0,
+                shift: 0,
+            }
+        } else if shift < -30 {
+            Requantization {
+                multiplier: i32::MAX,
+                shift: -30,
+            }
+        } else {
+            Requantization {
+                multiplier: multiplier as i32,
+                shift,
+            }
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        (self.multiplier as f64 / 2f64.powi(31 + self.shift)) as f32
+    }
+
+    /// Rescales an accumulator value, rounding half up and saturating to `i32`.
+    pub fn apply(&self, x: i32) -> i32 {
+        let total_shift = 31 + self.shift;
+        let product = x as i64 * self.multiplier as i64;
+        let rounded = (product + (1i64 << (total_shift - 1))) >> total_shift;
+        rounded.clamp(i32::MIN as i64, i32::MAX as i64) as i32
     }
 }
 
+/// Integer counterpart of [`Dense`].
 #[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct QuantizedLayer {
+pub struct QuantizedDense {
     pub weights: Array2<i8>,
     pub biases: Array1<i32>,
     pub activation: ActivationFunction,
-    pub weight_scale: f32,
-    pub bias_scale: f32,
+    pub weight_granularity: WeightGranularity,
+    /// One scale per output neuron; every entry is the same for per-tensor quantization.
+    pub weight_scales: Array1<f32>,
+    pub bias_scales: Array1<f32>,
+    /// `-input.zero_point * sum(row)`, added to the accumulator so it only sees `x_q - zp`.
+    pub zero_point_corrections: Array1<i32>,
+    pub input: QuantizationParams,
+    pub output: QuantizationParams,
+    pub requantizations: Vec<Requantization>,
+    /// Activation LUT already expressed in the `output` quantization, empty for activations
+    /// that are computed directly.
+    pub activation_table: Vec<i8>,
+    /// How sigmoid layers index `activation_table`.
+    pub sigmoid_lut: SigmoidLut,
+}
+
+impl QuantizedDense {
+    /// Integer matmul plus bias, each row on its own `bias_scales` grid.
+    pub fn accumulate(&self, input: &Array1<i8>) -> Array1<i32> {
+        self.weights
+            .mapv(|w| w as i32)
+            .dot(&input.mapv(|a| a as i32))
+            + &self.biases
+            + &self.zero_point_corrections
+    }
+
+    pub fn activate(&self, acc: &Array1<i32>) -> Array1<i8> {
+        let requantized = acc
+            .iter()
+            .zip(&self.requantizations)
+            .map(|(&x, requant)| requant.apply(x));
+        let zero_point = self.output.zero_point;
+
+        match self.activation {
+            ActivationFunction::Sigmoid => Array1::from_iter(
+                requantized.map(|x| self.sigmoid_lut.lookup(&self.activation_table, x)),
+            ),
+            ActivationFunction::ReLU => Array1::from_iter(
+                requantized.map(|x| (x + zero_point).clamp(zero_point.max(-128), 127) as i8),
+            ),
+            ActivationFunction::Linear => {
+                Array1::from_iter(requantized.map(|x| (x + zero_point).clamp(-128, 127) as i8))
+            }
+            ActivationFunction::LeakyReLU(slope) => {
+                let slope = (slope * 32768.0).round() as i64;
+                Array1::from_iter(requantized.map(|x| {
+                    let x = if x < 0 {
+                        ((x as i64 * slope + (1 << 14)) >> 15) as i32
+                    } else {
+                        x
+                    };
+                    (x + zero_point).clamp(-128, 127) as i8
+                }))
+            }
+            ActivationFunction::ClippedReLU(max) => {
+                let max = self.output.quantize(max) as i32;
+                Array1::from_iter(requantized.map(|x| {
+                    (x + zero_point).clamp(zero_point.max(-128), max.max(zero_point)) as i8
+                }))
+            }
+            ActivationFunction::Tanh
+            | ActivationFunction::HardSigmoid
+            | ActivationFunction::HardSwish
+            | ActivationFunction::SiLU
+            | ActivationFunction::GELU => Array1::from_iter(
+                requantized.map(|x| self.activation_table[(x.clamp(-128, 127) + 128) as usize]),
+            ),
+            ActivationFunction::Softmax => {
+                let logits: Vec<i32> = requantized.collect();
+                let max = logits.iter().copied().max().unwrap_or(0);
+                let table = softmax_exp_table();
+
+                let exp: Vec<i64> = logits
+                    .iter()
+                    .map(|&x| {
+                        let distance = (max as i64 - x as i64) as usize;
+                        table.get(distance).copied().unwrap_or(0) as i64
+                    })
+                    .collect();
+                let sum: i64 = exp.iter().sum();
+
+                // One output step is `1 / levels`, so scale the probabilities onto that grid.
+                let levels = (1.0 / self.output.scale).round() as i64;
+                Array1::from_iter(exp.iter().map(|&e| {
+                    let q = (e * levels + sum / 2) / sum;
+                    (q + zero_point as i64).clamp(-128, 127) as i8
+                }))
+            }
+        }
+    }
+
+    /// Requantized pre-activations; for softmax layers these are logits on a shared grid.
+    pub fn logits(&self, acc: &Array1<i32>) -> Array1<i32> {
+        Array1::from_iter(
+            acc.iter()
+                .zip(&self.requantizations)
+                .map(|(&x, requant)| requant.apply(x)),
+        )
+    }
+
+    pub fn feedforward(&self, input: &Array1<i8>) -> Array1<i8> {
+        self.activate(&self.accumulate(input))
+    }
+
+    /// Index of the largest output. Softmax layers are decided on their logits, so the `exp`
+    /// LUT and the normalization are skipped.
+    pub fn argmax(&self, input: &Array1<i8>) -> usize {
+        let acc = self.accumulate(input);
+        match self.activation {
+            ActivationFunction::Softmax => argmax(self.logits(&acc).iter()),
+            _ => argmax(self.activate(&acc).iter()),
+        }
+    }
+
+    /// The float weights this layer actually multiplies by.
+    pub fn dequantized_weights(&self) -> Array2<f32> {
+        let row_scales = self.weight_scales.view().insert_axis(Axis(1));
+        Zip::from(&self.weights)
+            .and_broadcast(&row_scales)
+            .map_collect(|&w, &scale| w as f32 * scale)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -204,63 +816,142 @@ impl QuantizedNeuralNetwork {
         let mut activations = input.clone();
 
         for layer in &self.layers {
-            let z = layer
-                .weights
-                .mapv(|w| w as i32)
-                .dot(&activations.mapv(|a| a as i32))
-                + &layer.biases;
-
-            activations = match layer.activation {
-                ActivationFunction::Sigmoid => z.mapv(|x| {
-                    let x = (x >> 7).clamp(-8, 8);
-                    let index = (x + 8) as usize;
-                    SIGMOID_INT_TABLE[index]
-                }),
-                ActivationFunction::ReLU => z.mapv(|x| ((x.max(0).min(127)) as i8)),
-                ActivationFunction::Linear => z.mapv(|x| ((x.clamp(-128, 127)) as i8)),
-            };
+            activations = layer.feedforward(&activations);
         }
 
         activations
     }
 
+    /// Index of the most likely class. Softmax output layers are decided on their logits, so
+    /// the `exp` LUT and the normalization are skipped.
+    pub fn predict(&self, input: &Array1<i8>) -> usize {
+        let Some((last, hidden)) = self.layers.split_last() else {
+            return argmax(input.iter());
+        };
+
+        let mut activations = input.clone();
+        for layer in hidden {
+            activations = layer.feedforward(&activations);
+        }
+
+        last.argmax(&activations)
+    }
+
+    pub fn input_params(&self) -> QuantizationParams {
+        self.layers
+            .first()
+            .map_or_else(QuantizationParams::default, QuantizedLayer::input)
+    }
+
+    pub fn output_params(&self) -> QuantizationParams {
+        self.layers
+            .last()
+            .map_or_else(QuantizationParams::default, QuantizedLayer::output)
+    }
+
+    pub fn quantize_input(&self, input: &Array1<f32>) -> Array1<i8> {
+        let params = self.input_params();
+        input.mapv(|x| params.quantize(x))
+    }
+
+    pub fn dequantize_output(&self, output: &Array1<i8>) -> Array1<f32> {
+        let params = self.output_params();
+        output.mapv(|q| params.dequantize(q))
+    }
+
     pub fn save_to_file(&self, path: &PathBuf) -> Result<()> {
-        let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, self)?;
-        Ok(())
+        save_with_magic(path, QUANTIZED_NETWORK_FILE_MAGIC, self)
     }
 
+    /// Writes the network in the JSON layout the RaySoC firmware loads. The SoC executes
+    /// dense layers with its sigmoid LUT, which gets the tables of the layers so it computes
+    /// exactly what [`QuantizedDense::feedforward`] does. A softmax output layer becomes a
+    /// sigmoid of its logits there, which predicts the same class.
     pub fn export_raysoc_network(&self, path: &PathBuf) -> Result<()> {
+        let mut dense_layers: Vec<&QuantizedDense> = Vec::new();
+        for (layer_idx, layer) in self.layers.iter().enumerate() {
+            let Some(dense) = layer.as_dense() else {
+                bail!(
+                    "Layer {} cannot run on RaySoC, which executes dense layers only.",
+                    layer_idx
+                );
+            };
+            let is_output = layer_idx == self.layers.len() - 1;
+            match dense.activation {
+                ActivationFunction::Sigmoid => {}
+                ActivationFunction::Softmax if is_output => {}
+                activation => bail!(
+                    "Layer {} uses {:?}, but RaySoC computes sigmoid activations only.",
+                    layer_idx,
+                    activation
+                ),
+            }
+            if let Some(first) = dense_layers.first() {
+                if dense.sigmoid_lut != first.sigmoid_lut {
+                    bail!(
+                        "Layer {} uses another sigmoid LUT layout than layer 0, but RaySoC has one sigmoid unit.",
+                        layer_idx
+                    );
+                }
+            }
+            dense_layers.push(dense);
+        }
+
         let mut layers = Vec::new();
 
-        if let Some(first_layer) = self.layers.first() {
+        if let Some(first_layer) = dense_layers.first() {
             let input_size = first_layer.weights.shape()[1];
             layers.push(input_size);
         } else {
             bail!("The neural network has no layers.");
         }
 
-        for layer in &self.layers {
+        for layer in &dense_layers {
             let output_size = layer.weights.shape()[0];
             layers.push(output_size);
         }
 
         let mut weights = Vec::new();
         let mut biases = Vec::new();
+        let mut multipliers = Vec::new();
+        let mut shifts = Vec::new();
+        let mut sigmoid_tables = Vec::new();
+        let mut zero_points = vec![self.input_params().zero_point];
 
-        for layer in &self.layers {
+        for layer in &dense_layers {
             let w = layer.weights.map(|&x| x as i32);
-            let weight_rows = w.shape()[0];
-            let weight_cols = w.shape()[1];
-            let mut weight_matrix = Vec::with_capacity(weight_rows);
+            let mut weight_matrix = Vec::with_capacity(w.shape()[0]);
 
             for row in w.outer_iter() {
                 weight_matrix.push(row.to_vec());
             }
             weights.push(weight_matrix);
 
-            biases.push(layer.biases.to_vec());
+            // The MAC array only adds a bias, so the zero-point correction is folded into it.
+            biases.push((&layer.biases + &layer.zero_point_corrections).to_vec());
+            zero_points.push(layer.output.zero_point);
+
+            let lut = layer.sigmoid_lut;
+            let (requantizations, table) = match layer.activation {
+                ActivationFunction::Sigmoid => (
+                    layer.requantizations.clone(),
+                    layer.activation_table.clone(),
+                ),
+                // Softmax output: the sigmoid keeps the order of the logits.
+                _ => (
+                    layer
+                        .bias_scales
+                        .iter()
+                        .map(|&bias_scale| {
+                            Requantization::from_scale(bias_scale / lut.input_scale())
+                        })
+                        .collect(),
+                    lut.table(layer.output),
+                ),
+            };
+            multipliers.push(requantizations.iter().map(|r| r.multiplier).collect());
+            shifts.push(requantizations.iter().map(|r| r.shift).collect());
+            sigmoid_tables.push(table.iter().map(|&entry| entry as i32).collect());
         }
 
         #[derive(Serialize)]
@@ -268,12 +959,23 @@ impl QuantizedNeuralNetwork {
             layers: Vec<usize>,
             weights: Vec<Vec<Vec<i32>>>,
             biases: Vec<Vec<i32>>,
+            multipliers: Vec<Vec<i32>>,
+            shifts: Vec<Vec<i32>>,
+            zero_points: Vec<i32>,
+            /// One table per layer, indexed as described by [`SigmoidLut`].
+            sigmoid_tables: Vec<Vec<i32>>,
+            sigmoid_interpolate: bool,
         }
 
         let network_data = RaySocNetworkData {
             layers,
             weights,
             biases,
+            multipliers,
+            shifts,
+            zero_points,
+            sigmoid_interpolate: dense_layers[0].sigmoid_lut.interpolate,
+            sigmoid_tables,
         };
 
         let json = serde_json::to_string_pretty(&network_data)?;
@@ -281,19 +983,75 @@ impl QuantizedNeuralNetwork {
         Ok(())
     }
 
+    /// Also reads files written before the magic existed, which hold dense layers without
+    /// requantization and compute exactly what they did then.
     pub fn load_from_file(path: &PathBuf) -> Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-
-        let net = bincode::deserialize_from(reader)?;
-        Ok(net)
+        load_with_magic(
+            path,
+            QUANTIZED_NETWORK_FILE_MAGIC,
+            "quantized network",
+            |layers: Vec<LegacyQuantizedLayer>| QuantizedNeuralNetwork {
+                layers: layers
+                    .into_iter()
+                    .map(|layer| QuantizedLayer::Dense(layer.into()))
+                    .collect(),
+            },
+        )
     }
 }
 
-const SIGMOID_INT_TABLE: [i8; 17] = [
+/// Quantized dense layer as stored before file magics existed. Accumulators went straight
+/// into the activation: the sigmoid looked `acc >> 7` up in [`LEGACY_SIGMOID_TABLE`] and ReLU
+/// and linear layers clamped to `i8`.
+#[derive(Deserialize)]
+struct LegacyQuantizedLayer {
+    weights: Array2<i8>,
+    biases: Array1<i32>,
+    activation: ActivationFunction,
+    weight_scale: f32,
+    bias_scale: f32,
+}
+
+const LEGACY_SIGMOID_TABLE: [i8; 17] = [
     0, 4, 8, 15, 26, 41, 60, 81, 103, 122, 127, 127, 127, 127, 127, 127, 127,
 ];
 
+impl From<LegacyQuantizedLayer> for QuantizedDense {
+    /// A requantization by 1 keeps the accumulator, which the default [`SigmoidLut`] then
+    /// indexes the same way the hand-written table was.
+    fn from(layer: LegacyQuantizedLayer) -> Self {
+        let outputs = layer.weights.nrows();
+        QuantizedDense {
+            activation_table: match layer.activation {
+                ActivationFunction::Sigmoid => LEGACY_SIGMOID_TABLE.to_vec(),
+                _ => Vec::new(),
+            },
+            weights: layer.weights,
+            biases: layer.biases,
+            activation: layer.activation,
+            weight_granularity: WeightGranularity::PerTensor,
+            weight_scales: Array1::from_elem(outputs, layer.weight_scale),
+            bias_scales: Array1::from_elem(outputs, layer.bias_scale),
+            zero_point_corrections: Array1::zeros(outputs),
+            input: QuantizationParams::default(),
+            output: QuantizationParams::default(),
+            requantizations: vec![Requantization::from_scale(1.0); outputs],
+            sigmoid_lut: SigmoidLut::default(),
+        }
+    }
+}
+
+/// First index of the largest element.
+fn argmax<T: PartialOrd + Copy>(values: impl Iterator<Item = T>) -> usize {
+    let mut best: Option<(usize, T)> = None;
+    for (index, value) in values.enumerate() {
+        if best.is_none_or(|(_, best)| value > best) {
+            best = Some((index, value));
+        }
+    }
+    best.map_or(0, |(index, _)| index)
+}
+
 #[derive(Clone)]
 pub struct DataPoint {
     pub inputs: Array1<f32>,
@@ -302,243 +1060,643 @@ pub struct DataPoint {
 
 pub struct Trainer<'a> {
     pub network: &'a mut NeuralNetwork,
+    /// Base learning rate, scaled by `schedule`.
     pub learning_rate: f32,
-    pub momentum: f32,
-    pub weight_velocities: Vec<Array2<f32>>,
-    pub bias_velocities: Vec<Array1<f32>>,
+    pub schedule: Box<dyn LearningRateSchedule>,
+    pub optimizer: Box<dyn Optimizer>,
+    pub loss: Box<dyn Loss>,
+    /// Weight penalties by layer index; layers past the end are not penalized.
+    pub regularization: Vec<Regularization>,
+    /// Quantization that `fine_tune` trains against.
+    pub quantization: QuantizationConfig,
+    /// How `fine_tune` picks activation ranges every epoch; `None` uses the `[-1, 1]` default
+    /// of [`NeuralNetwork::quantize`].
+    pub calibration: Option<CalibrationMethod>,
+    /// Samples per update; `None` averages the gradient over the whole dataset.
+    pub batch_size: Option<usize>,
+    /// Seeds the per-epoch shuffle of the mini-batches and the dropout masks.
+    pub seed: u64,
+    /// Epochs completed so far, counted across `train` and `fine_tune` calls.
+    pub epoch: usize,
+    /// Optimizer updates made so far.
+    pub step: usize,
+    /// Held-out data evaluated every `validation_interval` epochs and after the last one.
+    pub validation: Option<&'a [DataPoint]>,
+    pub validation_interval: usize,
+    /// Stops training once the validation metric stops improving; needs `validation`.
+    pub early_stopping: Option<EarlyStopping>,
+    /// Progress of `early_stopping`, kept in checkpoints and cleared when a call ends.
+    pub early_stopping_state: Option<EarlyStoppingState>,
+    /// Receive per-epoch metrics; a [`ConsoleLogger`] by default.
+    pub callbacks: Vec<Box<dyn Callback>>,
+    /// Where to write a [`Checkpoint`] every `checkpoint_interval` epochs and at the end of
+    /// every `train` or `fine_tune` call.
+    pub checkpoint_path: Option<PathBuf>,
+    pub checkpoint_interval: usize,
+    /// How per-sample gradients are summed; the default makes seeded runs reproducible
+    /// whatever the thread count or CPU.
+    pub reduction: Reduction,
+}
+
+/// Samples whose gradients are computed together, as one matrix per layer. Fixed rather than
+/// derived from the thread count, so the summation order is too.
+const GRADIENT_CHUNK: usize = 32;
+
+/// Gradients of every network parameter, in [`NeuralNetwork::parameters`] order, and the
+/// loss, summed over some samples.
+type Gradients = (Vec<ArrayD<f32>>, f32);
+
+/// Adds `b` to the gradient sum `a`.
+fn add_gradients(mut a: Gradients, b: Gradients) -> Gradients {
+    for (a, b) in a.0.iter_mut().zip(&b.0) {
+        *a += b;
+    }
+    a.1 += b.1;
+    a
+}
+
+/// Stacks equally long vectors into the rows of a matrix.
+fn stack_rows<'a>(rows: impl Iterator<Item = &'a Array1<f32>>) -> Array2<f32> {
+    let rows: Vec<_> = rows.map(Array1::view).collect();
+    nd::stack(Axis(0), &rows).expect("all rows must have the same length")
+}
+
+/// Outcome of one optimizer update.
+struct Update {
+    learning_rate: f32,
+    /// Norm of the averaged gradient, penalties included.
+    gradient_norm: f32,
+    /// Weight penalty before the update.
+    penalty: f32,
+}
+
+/// Running totals of one epoch, turned into averages by [`EpochStats::finish`].
+#[derive(Default)]
+struct EpochStats {
+    loss: f32,
+    regularization: f32,
+    learning_rate: f32,
+    gradient_norm: f32,
+    updates: usize,
+}
+
+impl EpochStats {
+    fn record_update(&mut self, update: Update) {
+        let Update {
+            learning_rate,
+            gradient_norm,
+            penalty,
+        } = update;
+        self.regularization += penalty;
+        self.learning_rate = learning_rate;
+        self.gradient_norm += gradient_norm;
+        self.updates += 1;
+    }
+
+    fn finish(self, samples: usize) -> Self {
+        EpochStats {
+            loss: self.loss / samples as f32,
+            regularization: self.regularization / self.updates.max(1) as f32,
+            gradient_norm: self.gradient_norm / self.updates.max(1) as f32,
+            ..self
+        }
+    }
 }
 
 impl<'a> Trainer<'a> {
+    /// SGD with momentum on [`HalfSquaredError`].
     pub fn new(network: &'a mut NeuralNetwork, learning_rate: f32, momentum: f32) -> Self {
-        let weight_velocities = network
-            .layers
-            .iter()
-            .map(|layer| Array2::zeros(layer.weights.raw_dim()))
-            .collect();
+        Self::with_loss(network, learning_rate, momentum, HalfSquaredError)
+    }
 
-        let bias_velocities = network
-            .layers
-            .iter()
-            .map(|layer| Array1::zeros(layer.biases.len()))
-            .collect();
+    pub fn with_loss(
+        network: &'a mut NeuralNetwork,
+        learning_rate: f32,
+        momentum: f32,
+        loss: impl Loss + 'static,
+    ) -> Self {
+        Self::with_optimizer(network, learning_rate, Sgd::new(momentum), loss)
+    }
 
+    pub fn with_optimizer(
+        network: &'a mut NeuralNetwork,
+        learning_rate: f32,
+        optimizer: impl Optimizer + 'static,
+        loss: impl Loss + 'static,
+    ) -> Self {
         Trainer {
             network,
             learning_rate,
-            momentum,
-            weight_velocities,
-            bias_velocities,
+            schedule: Box::new(Constant),
+            optimizer: Box::new(optimizer),
+            loss: Box::new(loss),
+            regularization: Vec::new(),
+            quantization: QuantizationConfig::default(),
+            calibration: None,
+            batch_size: None,
+            seed: 0,
+            epoch: 0,
+            step: 0,
+            validation: None,
+            validation_interval: 1,
+            early_stopping: None,
+            early_stopping_state: None,
+            callbacks: vec![Box::new(ConsoleLogger::default())],
+            checkpoint_path: None,
+            checkpoint_interval: 1,
+            reduction: Reduction::default(),
         }
     }
 
-    pub fn train(&mut self, data: &[DataPoint], epochs: usize) {
-        for epoch in 0..epochs {
-            let (total_weight_grads, total_bias_grads, total_error) = data
-                .par_iter()
-                .map(|point| self.compute_gradients(point))
-                .reduce(
-                    || {
-                        (
-                            vec![Array2::zeros((0, 0)); self.network.layers.len()],
-                            vec![Array1::zeros(0); self.network.layers.len()],
-                            0.0f32,
-                        )
-                    },
-                    |(mut w1, mut b1, e1), (w2, b2, e2)| {
-                        for i in 0..w1.len() {
-                            if w1[i].is_empty() {
-                                w1[i] = w2[i].clone();
-                                b1[i] = b2[i].clone();
-                            } else {
-                                w1[i] += &w2[i];
-                                b1[i] += &b2[i];
-                            }
-                        }
-                        (w1, b1, e1 + e2)
-                    },
-                );
-
-            let data_len = data.len() as f32;
-
-            for (i, layer) in self.network.layers.iter_mut().enumerate() {
-                let grad_w = &total_weight_grads[i] * (self.learning_rate / data_len);
-                let grad_b = &total_bias_grads[i] * (self.learning_rate / data_len);
+    pub fn train(&mut self, data: &[DataPoint], epochs: usize) -> Result<()> {
+        self.fit(data, epochs, TrainingPhase::Train)
+    }
 
-                self.weight_velocities[i] = &self.weight_velocities[i] * self.momentum - &grad_w;
-                self.bias_velocities[i] = &self.bias_velocities[i] * self.momentum - &grad_b;
+    /// Quantization-aware training: the forward pass runs the integer network the SoC would
+    /// execute and gradients reach the float weights through a straight-through estimator.
+    /// Dropout and reshape layers vanish from the integer network, so they are skipped.
+    pub fn fine_tune(&mut self, data: &[DataPoint], epochs: usize) -> Result<()> {
+        if let Some(layer_idx) = self
+            .network
+            .layers
+            .iter()
+            .position(|layer| matches!(layer, NetworkLayer::BatchNorm(_)))
+        {
+            bail!(
+                "Layer {} is a batch normalization; fold it with NeuralNetwork::fold_batch_norm before quantization-aware training.",
+                layer_idx
+            );
+        }
+        if let Some(layer_idx) = self.network.layers.iter().position(|layer| {
+            !matches!(
+                layer,
+                NetworkLayer::Dense(_) | NetworkLayer::Dropout(_) | NetworkLayer::Reshape(_)
+            )
+        }) {
+            bail!(
+                "Layer {} is not dense; quantization-aware training supports dense layers only.",
+                layer_idx
+            );
+        }
+        self.fit(data, epochs, TrainingPhase::FineTune)
+    }
 
-                layer.weights += &self.weight_velocities[i];
-                layer.biases += &self.bias_velocities[i];
-            }
+    fn fit(&mut self, data: &[DataPoint], epochs: usize, phase: TrainingPhase) -> Result<()> {
+        let start = Instant::now();
+        let interval = self.validation_interval.max(1);
+        let mut summary = TrainingSummary {
+            phase,
+            epochs: 0,
+            best_epoch: None,
+            stopped_early: false,
+            last: None,
+        };
 
-            if epoch % 5 == 0 || epoch == epochs - 1 {
-                println!("Epoch {}: Error = {}", epoch, total_error / data_len);
+        if phase == TrainingPhase::FineTune {
+            for warning in self.network.quantization_warnings(&self.quantization) {
+                for callback in &mut self.callbacks {
+                    callback.on_warning(&warning)?;
+                }
             }
         }
-    }
 
-    pub fn fine_tune(&mut self, data: &[DataPoint], epochs: usize) {
         for epoch in 0..epochs {
-            let quant_network = self.network.quantize();
-
-            let (total_weight_grads, total_bias_grads, total_error) = data
-                .par_iter()
-                .map(|point| self.compute_gradients_quantized(point, &quant_network))
-                .reduce(
-                    || {
-                        (
-                            vec![Array2::zeros((0, 0)); self.network.layers.len()],
-                            vec![Array1::zeros(0); self.network.layers.len()],
-                            0.0f32,
-                        )
-                    },
-                    |(mut w1, mut b1, e1), (w2, b2, e2)| {
-                        for i in 0..w1.len() {
-                            if w1[i].is_empty() {
-                                w1[i] = w2[i].clone();
-                                b1[i] = b2[i].clone();
-                            } else {
-                                w1[i] += &w2[i];
-                                b1[i] += &b2[i];
-                            }
-                        }
-                        (w1, b1, e1 + e2)
-                    },
-                );
+            let epoch_start = Instant::now();
+
+            let (stats, quant_network) = match phase {
+                TrainingPhase::Train => (self.float_epoch(data), None),
+                TrainingPhase::FineTune => {
+                    let calibration = match self.calibration {
+                        Some(method) => self.network.calibrate(data, method),
+                        None => Calibration::uniform(
+                            self.network.layers.len(),
+                            ActivationRange::default(),
+                        ),
+                    };
+                    let stats = self.quantized_epoch(data, &calibration)?;
+                    let quant_network = self
+                        .network
+                        .quantize_with(&calibration, &self.quantization)?;
+                    (stats, Some(quant_network))
+                }
+            };
 
-            let data_len = data.len() as f32;
+            let validation = self
+                .validation
+                .filter(|_| (self.epoch + 1).is_multiple_of(interval) || epoch == epochs - 1)
+                .map(|validation| match &quant_network {
+                    Some(quant_network) => quant_network.evaluate(validation, self.loss.as_ref()),
+                    None => self.network.evaluate(validation, self.loss.as_ref()),
+                });
+
+            match validation {
+                Some(evaluation) => self.schedule.end_epoch(evaluation.loss),
+                None if self.validation.is_none() => self.schedule.end_epoch(stats.loss),
+                None => {}
+            }
 
-            for (i, layer) in self.network.layers.iter_mut().enumerate() {
-                let grad_w = &total_weight_grads[i] * (self.learning_rate / data_len);
-                let grad_b = &total_bias_grads[i] * (self.learning_rate / data_len);
+            let metrics = EpochMetrics {
+                phase,
+                epoch: self.epoch,
+                loss: stats.loss,
+                regularization: stats.regularization,
+                validation,
+                learning_rate: stats.learning_rate,
+                gradient_norm: stats.gradient_norm,
+                epoch_seconds: epoch_start.elapsed().as_secs_f64(),
+                elapsed_seconds: start.elapsed().as_secs_f64(),
+            };
+            self.epoch += 1;
+            summary.epochs += 1;
 
-                self.weight_velocities[i] = &self.weight_velocities[i] * self.momentum - &grad_w;
-                self.bias_velocities[i] = &self.bias_velocities[i] * self.momentum - &grad_b;
+            for callback in &mut self.callbacks {
+                callback.on_epoch_end(&metrics)?;
+            }
 
-                layer.weights += &self.weight_velocities[i];
-                layer.biases += &self.bias_velocities[i];
+            let mut stop = false;
+            if let (Some(stopping), Some(evaluation)) = (self.early_stopping, validation) {
+                let value = stopping.metric.value(&evaluation);
+                let improved = self.early_stopping_state.as_ref().is_none_or(|best| {
+                    stopping
+                        .metric
+                        .improves(value, best.value, stopping.min_delta)
+                });
+                if improved {
+                    self.early_stopping_state = Some(EarlyStoppingState {
+                        value,
+                        epoch: metrics.epoch,
+                        layers: self.network.layers.clone(),
+                    });
+                }
+
+                let best_epoch = self
+                    .early_stopping_state
+                    .as_ref()
+                    .map_or(metrics.epoch, |best| best.epoch);
+                stop = metrics.epoch - best_epoch >= stopping.patience;
             }
+            summary.last = Some(metrics);
 
-            if epoch % 5 == 0 || epoch == epochs - 1 {
-                println!(
-                    "Fine-Tuning Epoch {}: Error = {}",
-                    epoch,
-                    total_error / data_len
-                );
+            if let Some(path) = &self.checkpoint_path {
+                if self.epoch.is_multiple_of(self.checkpoint_interval.max(1)) {
+                    self.save_checkpoint(path)?;
+                }
+            }
+            if stop {
+                summary.stopped_early = true;
+                break;
             }
         }
-    }
 
-    fn compute_gradients(
-        &self,
-        data_point: &DataPoint,
-    ) -> (Vec<Array2<f32>>, Vec<Array1<f32>>, f32) {
-        let mut activations = Vec::new();
-        let mut zs = Vec::new();
+        // Written before the best weights are restored, so like every other checkpoint it holds
+        // the last weights along with the early stopping progress.
+        if let Some(path) = &self.checkpoint_path {
+            self.save_checkpoint(path)?;
+        }
 
-        activations.push(data_point.inputs.clone());
+        if let (Some(stopping), Some(best)) =
+            (self.early_stopping, self.early_stopping_state.take())
+        {
+            summary.best_epoch = Some(best.epoch);
+            if stopping.restore_best {
+                self.network.layers = best.layers;
+            }
+        }
 
-        for layer in &self.network.layers {
-            let z = layer.weights.dot(activations.last().unwrap()) + &layer.biases;
-            zs.push(z.clone());
-            activations.push(layer.activation.activate(&z));
+        for callback in &mut self.callbacks {
+            callback.on_train_end(&summary)?;
         }
 
-        let output_activations = activations.last().unwrap();
-        let delta = output_activations - &data_point.targets;
+        Ok(())
+    }
+
+    fn float_epoch(&mut self, data: &[DataPoint]) -> EpochStats {
+        let mut stats = EpochStats::default();
+
+        for batch in self.epoch_batches(data.len()) {
+            let (gradients, error) = self.sum_gradients(&batch, |samples, gradients| {
+                self.compute_gradients(data, samples, gradients)
+            });
+            stats.loss += error;
+            let update = self.apply_gradients(gradients, batch.len() as f32);
+            stats.record_update(update);
+        }
 
-        let error = delta.mapv(|e| e * e).sum() / delta.len() as f32;
+        // Validation and checkpoints see the statistics of the finished epoch's weights.
+        self.network.update_statistics(data);
+        stats.finish(data.len())
+    }
 
-        let mut nabla_b = Vec::new();
-        let mut nabla_w = Vec::new();
+    fn quantized_epoch(
+        &mut self,
+        data: &[DataPoint],
+        calibration: &Calibration,
+    ) -> Result<EpochStats> {
+        let mut stats = EpochStats::default();
 
-        let mut delta = delta
-            * self
+        for batch in self.epoch_batches(data.len()) {
+            // Ranges stay fixed for the epoch, the weights are requantized after every update.
+            let quant_network = self
                 .network
+                .quantize_with(calibration, &self.quantization)?;
+            let quant_layers: Vec<&QuantizedDense> = quant_network
                 .layers
-                .last()
-                .unwrap()
-                .activation
-                .derivative(zs.last().unwrap());
-
-        for l in (0..self.network.layers.len()).rev() {
-            let layer = &self.network.layers[l];
-            nabla_b.insert(0, delta.clone());
-            let a_prev = &activations[l];
-            let nabla_w_l = delta
-                .view()
-                .insert_axis(Axis(1))
-                .dot(&a_prev.view().insert_axis(Axis(0)));
-            nabla_w.insert(0, nabla_w_l);
+                .iter()
+                .map(|layer| layer.as_dense().expect("fine_tune checks for dense layers"))
+                .collect();
+
+            let fake_quant_weights: Vec<Array2<f32>> = quant_layers
+                .iter()
+                .map(|layer| layer.dequantized_weights())
+                .collect();
+
+            let (gradients, error) = self.sum_gradients(&batch, |samples, gradients| {
+                for &i in samples {
+                    self.compute_gradients_quantized(
+                        &data[i],
+                        &quant_layers,
+                        &fake_quant_weights,
+                        gradients,
+                    );
+                }
+            });
+            stats.loss += error;
+            let update = self.apply_gradients(gradients, batch.len() as f32);
+            stats.record_update(update);
+        }
 
-            if l > 0 {
-                let z = &zs[l - 1];
-                let sp = self.network.layers[l - 1].activation.derivative(z);
-                delta = layer.weights.t().dot(&delta) * sp;
+        Ok(stats.finish(data.len()))
+    }
+
+    /// Splits the sample indices into the batches of the current epoch. Mini-batches are drawn
+    /// from an order shuffled by a generator seeded with `seed` and the epoch number, so a run
+    /// is reproducible and picks up the same order when resumed.
+    fn epoch_batches(&self, len: usize) -> Vec<Vec<usize>> {
+        let mut order: Vec<usize> = (0..len).collect();
+
+        let batch_size = match self.batch_size {
+            Some(size) if size < len => size.max(1),
+            _ => return vec![order],
+        };
+
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(self.epoch as u64));
+        order.shuffle(&mut rng);
+
+        order.chunks(batch_size).map(<[usize]>::to_vec).collect()
+    }
+
+    /// Splits `batch` into chunks of [`GRADIENT_CHUNK`] samples and has `add` add the
+    /// gradients of every chunk to an accumulator, in parallel. Accumulators are reused for
+    /// many chunks, see [`Reduction::accumulate`]. Networks with a [`BatchNorm`] need the
+    /// statistics of the whole mini-batch, so they compute it as one chunk.
+    fn sum_gradients<F>(&self, batch: &[usize], add: F) -> Gradients
+    where
+        F: Fn(&[usize], &mut Gradients) + Sync,
+    {
+        let has_batch_norm = self
+            .network
+            .layers
+            .iter()
+            .any(|layer| matches!(layer, NetworkLayer::BatchNorm(_)));
+        let chunk = if has_batch_norm {
+            batch.len().max(1)
+        } else {
+            GRADIENT_CHUNK
+        };
+        let chunks: Vec<&[usize]> = batch.chunks(chunk).collect();
+        self.reduction.accumulate(
+            chunks.len(),
+            || self.zero_gradients(),
+            |gradients, i| add(chunks[i], gradients),
+            add_gradients,
+        )
+    }
+
+    fn zero_gradients(&self) -> Gradients {
+        let parameters = self.network.parameters();
+        let zeros = parameters
+            .iter()
+            .map(|parameter| ArrayD::zeros(parameter.raw_dim()))
+            .collect();
+        (zeros, 0.0)
+    }
+
+    /// Averages the summed gradients over `count` samples, adds the weight penalties and hands
+    /// them to the optimizer.
+    fn apply_gradients(&mut self, gradients: Vec<ArrayD<f32>>, count: f32) -> Update {
+        let learning_rate = self
+            .schedule
+            .learning_rate(self.learning_rate, self.epoch, self.step);
+        self.step += 1;
+        self.optimizer.begin_step();
+        let penalties = self.parameter_penalties();
+        let mut squared_norm = 0.0;
+        let mut penalty = 0.0;
+
+        for (slot, ((parameter, gradient), regularization)) in self
+            .network
+            .parameters_mut()
+            .into_iter()
+            .zip(gradients)
+            .zip(penalties)
+            .enumerate()
+        {
+            let mut gradient = gradient / count;
+            if let Some(regularization) = regularization {
+                penalty += regularization.penalty(&parameter.view());
+                gradient += &regularization.gradient(&parameter.view());
             }
+            squared_norm += gradient.iter().map(|g| g * g).sum::<f32>();
+
+            self.optimizer
+                .update(slot, parameter, gradient.view(), learning_rate);
         }
 
-        (nabla_w, nabla_b, error)
+        Update {
+            learning_rate,
+            gradient_norm: squared_norm.sqrt(),
+            penalty,
+        }
     }
 
-    fn compute_gradients_quantized(
-        &self,
-        data_point: &DataPoint,
-        quant_network: &QuantizedNeuralNetwork,
-    ) -> (Vec<Array2<f32>>, Vec<Array1<f32>>, f32) {
-        let quant_inputs = data_point
-            .inputs
-            .mapv(|x| (x * 127.0).round().clamp(-128.0, 127.0) as i8);
+    /// The [`Regularization`] of every network parameter, in [`NeuralNetwork::parameters`]
+    /// order; `None` for parameters without a penalty.
+    fn parameter_penalties(&self) -> Vec<Option<Regularization>> {
+        let mut penalties = Vec::new();
+        for (layer_idx, layer) in self.network.layers.iter().enumerate() {
+            let regularization = self
+                .regularization
+                .get(layer_idx)
+                .filter(|regularization| !regularization.is_none());
+            let penalized = layer.penalized_parameters();
+            penalties.extend((0..layer.parameters().len()).map(|index| {
+                regularization
+                    .filter(|_| penalized.contains(&index))
+                    .copied()
+            }));
+        }
+        penalties
+    }
 
-        let quant_outputs = quant_network.feedforward(&quant_inputs);
+    /// Generator for the dropout masks of the chunk starting with sample `first` in the
+    /// current update. It depends on neither thread nor chunk order, so seeded runs stay
+    /// reproducible.
+    fn chunk_rng(&self, first: usize) -> StdRng {
+        let mut seed = [0; 32];
+        seed[..8].copy_from_slice(&self.seed.to_le_bytes());
+        seed[8..16].copy_from_slice(&(self.step as u64).to_le_bytes());
+        seed[16..24].copy_from_slice(&(first as u64).to_le_bytes());
+        StdRng::from_seed(seed)
+    }
 
-        let output_float = quant_outputs.mapv(|x| (x as f32) / 127.0);
+    /// Adds the gradients and loss of the samples at `indices` to `gradients`. The samples are
+    /// stacked into rows, so every layer runs once per direction for the whole chunk.
+    fn compute_gradients(&self, data: &[DataPoint], indices: &[usize], gradients: &mut Gradients) {
+        let Some(&first) = indices.first() else {
+            return;
+        };
+        let layers = &self.network.layers;
+        let targets = stack_rows(indices.iter().map(|&i| &data[i].targets));
+
+        let mut inputs = Vec::with_capacity(layers.len());
+        let mut caches = Vec::with_capacity(layers.len());
+        let mut activations = stack_rows(indices.iter().map(|&i| &data[i].inputs));
+        let mut rng = self.chunk_rng(first);
+
+        for layer in layers {
+            let (output, cache) = layer.forward_train(&activations, &mut rng);
+            inputs.push(activations);
+            caches.push(cache);
+            activations = output;
+        }
 
-        let delta = &output_float - &data_point.targets;
-        let error = delta.mapv(|e| e * e).sum() / delta.len() as f32;
+        let output = activations;
+        let (parameter_grads, error) = gradients;
+        *error += self.loss.batch_value(&output, &targets);
 
-        let mut activations = Vec::new();
-        let mut zs = Vec::new();
+        // The gradients of layer `l` end where those of layer `l + 1` start.
+        let mut end = parameter_grads.len();
+        let mut layer_grads = |layer: &NetworkLayer| {
+            let count = layer.parameters().len();
+            end -= count;
+            end..end + count
+        };
 
-        activations.push(data_point.inputs.clone());
+        let last = layers.len() - 1;
+        let range = layer_grads(&layers[last]);
+        let mut grad = match layers[last].output_activation(&caches[last]) {
+            Some((activation, z)) => {
+                let delta = self
+                    .loss
+                    .batch_output_delta(z, &output, &targets, activation);
+                layers[last].backward_pre_activation(
+                    &inputs[last],
+                    &caches[last],
+                    &delta,
+                    &mut parameter_grads[range],
+                    last > 0,
+                )
+            }
+            None => {
+                let grad = self.loss.batch_gradient(&output, &targets);
+                layers[last].backward(
+                    &inputs[last],
+                    &caches[last],
+                    &grad,
+                    &mut parameter_grads[range],
+                    last > 0,
+                )
+            }
+        };
+
+        for l in (0..last).rev() {
+            let output_grad = grad.expect("input gradient was requested");
+            let range = layer_grads(&layers[l]);
+            grad = layers[l].backward(
+                &inputs[l],
+                &caches[l],
+                &output_grad,
+                &mut parameter_grads[range],
+                l > 0,
+            );
+        }
+    }
 
-        for layer in &self.network.layers {
-            let z = layer.weights.dot(activations.last().unwrap()) + &layer.biases;
-            zs.push(z.clone());
-            activations.push(layer.activation.activate(&z));
+    fn compute_gradients_quantized(
+        &self,
+        data_point: &DataPoint,
+        layers: &[&QuantizedDense],
+        fake_quant_weights: &[Array2<f32>],
+        gradients: &mut Gradients,
+    ) {
+        let mut inputs = Vec::new();
+        let mut zs = Vec::new();
+        let mut pass_through = Vec::new();
+
+        let input_params = layers[0].input;
+        let mut activations = data_point.inputs.mapv(|x| input_params.quantize(x));
+
+        for layer in layers {
+            inputs.push(activations.mapv(|q| layer.input.dequantize(q)));
+
+            let acc = layer.accumulate(&activations);
+            zs.push(
+                Zip::from(&acc)
+                    .and(&layer.bias_scales)
+                    .map_collect(|&acc, &scale| acc as f32 * scale),
+            );
+
+            activations = layer.activate(&acc);
+
+            // Values clamped to the edge of the int8 range do not move when the
+            // pre-activation changes, so the straight-through estimator stops there.
+            pass_through.push(match layer.activation {
+                ActivationFunction::Sigmoid
+                | ActivationFunction::Softmax
+                | ActivationFunction::Tanh
+                | ActivationFunction::HardSigmoid => Array1::ones(activations.len()),
+                ActivationFunction::ReLU
+                | ActivationFunction::Linear
+                | ActivationFunction::LeakyReLU(_)
+                | ActivationFunction::ClippedReLU(_)
+                | ActivationFunction::HardSwish
+                | ActivationFunction::SiLU
+                | ActivationFunction::GELU => {
+                    activations.mapv(|q| if q == -128 || q == 127 { 0.0 } else { 1.0 })
+                }
+            });
         }
 
-        let mut delta_fp = &output_float - &data_point.targets;
+        let last = layers.len() - 1;
+        let output_params = layers[last].output;
+        let output_float = activations.mapv(|q| output_params.dequantize(q));
 
-        let mut nabla_b = Vec::new();
-        let mut nabla_w = Vec::new();
+        let (parameter_grads, error) = gradients;
+        *error += self.loss.value(&output_float, &data_point.targets);
 
-        delta_fp = delta_fp
-            * self
-                .network
-                .layers
-                .last()
-                .unwrap()
-                .activation
-                .derivative(zs.last().unwrap());
-
-        for l in (0..self.network.layers.len()).rev() {
-            let layer = &self.network.layers[l];
-            nabla_b.insert(0, delta_fp.clone());
-            let a_prev = &activations[l];
-            let nabla_w_l = delta_fp
-                .view()
-                .insert_axis(Axis(1))
-                .dot(&a_prev.view().insert_axis(Axis(0)));
-            nabla_w.insert(0, nabla_w_l);
+        // Every layer is dense and quantizes to one integer layer, so weight and bias of
+        // quantized layer `l` are parameters `2l` and `2l + 1`.
+
+        let mut delta = self.loss.output_delta(
+            &zs[last],
+            &output_float,
+            &data_point.targets,
+            layers[last].activation,
+        ) * &pass_through[last];
+
+        for l in (0..layers.len()).rev() {
+            matmul_add(
+                delta.view().insert_axis(Axis(1)),
+                inputs[l].view().insert_axis(Axis(0)),
+                matrix_grad(&mut parameter_grads[2 * l]),
+            );
+            parameter_grads[2 * l + 1] += &delta;
 
             if l > 0 {
-                let z = &zs[l - 1];
-                let sp = self.network.layers[l - 1].activation.derivative(z);
-                delta_fp = layer.weights.t().dot(&delta_fp) * sp;
+                let activation = layers[l - 1].activation;
+                delta = activation.backward(&zs[l - 1], &fake_quant_weights[l].t().dot(&delta))
+                    * &pass_through[l - 1];
             }
         }
-
-        (nabla_w, nabla_b, error)
     }
 }
@@ -0,0 +1,361 @@
+use crate::nd::{Array1, Array2, Axis};
+use crate::ActivationFunction;
+
+/// Smallest probability fed to `ln` or used as a divisor so a confidently wrong output stays
+/// finite.
+const PROBABILITY_EPSILON: f32 = 1e-7;
+
+pub trait Loss: Send + Sync {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32;
+
+    /// Gradient of [`Loss::value`] with respect to `output`.
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32>;
+
+    /// Gradient with respect to the output layer's pre-activation `z`. Losses with a simpler
+    /// closed form for a specific activation override this.
+    fn output_delta(
+        &self,
+        z: &Array1<f32>,
+        output: &Array1<f32>,
+        target: &Array1<f32>,
+        activation: ActivationFunction,
+    ) -> Array1<f32> {
+        activation.backward(z, &self.gradient(output, target))
+    }
+
+    /// Sum of [`Loss::value`] over a batch with one sample per row.
+    fn batch_value(&self, output: &Array2<f32>, target: &Array2<f32>) -> f32 {
+        output
+            .rows()
+            .into_iter()
+            .zip(target.rows())
+            .map(|(output, target)| self.value(&output.to_owned(), &target.to_owned()))
+            .sum()
+    }
+
+    /// [`Loss::gradient`] for a batch with one sample per row.
+    fn batch_gradient(&self, output: &Array2<f32>, target: &Array2<f32>) -> Array2<f32> {
+        let mut gradient = Array2::zeros(output.raw_dim());
+        for ((mut gradient, output), target) in gradient
+            .rows_mut()
+            .into_iter()
+            .zip(output.rows())
+            .zip(target.rows())
+        {
+            gradient.assign(&self.gradient(&output.to_owned(), &target.to_owned()));
+        }
+        gradient
+    }
+
+    /// [`Loss::output_delta`] for a batch with one sample per row.
+    fn batch_output_delta(
+        &self,
+        z: &Array2<f32>,
+        output: &Array2<f32>,
+        target: &Array2<f32>,
+        activation: ActivationFunction,
+    ) -> Array2<f32> {
+        output_delta_rows(self, z, output, target, activation)
+    }
+}
+
+/// Applies [`Loss::output_delta`] to every row of a batch.
+fn output_delta_rows<L: Loss + ?Sized>(
+    loss: &L,
+    z: &Array2<f32>,
+    output: &Array2<f32>,
+    target: &Array2<f32>,
+    activation: ActivationFunction,
+) -> Array2<f32> {
+    let mut delta = Array2::zeros(output.raw_dim());
+    for (((mut delta, z), output), target) in delta
+        .rows_mut()
+        .into_iter()
+        .zip(z.rows())
+        .zip(output.rows())
+        .zip(target.rows())
+    {
+        delta.assign(&loss.output_delta(
+            &z.to_owned(),
+            &output.to_owned(),
+            &target.to_owned(),
+            activation,
+        ));
+    }
+    delta
+}
+
+/// Half the squared error summed over the outputs. Its gradient is the raw difference
+/// `output - target` the trainer stepped along before losses were pluggable, so
+/// [`crate::Trainer::new`] uses it and learning rates tuned back then keep their meaning.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HalfSquaredError;
+
+impl Loss for HalfSquaredError {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32 {
+        0.5 * (output - target).mapv(|e| e * e).sum()
+    }
+
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32> {
+        output - target
+    }
+
+    fn batch_value(&self, output: &Array2<f32>, target: &Array2<f32>) -> f32 {
+        0.5 * (output - target).mapv(|e| e * e).sum()
+    }
+
+    fn batch_output_delta(
+        &self,
+        z: &Array2<f32>,
+        output: &Array2<f32>,
+        target: &Array2<f32>,
+        activation: ActivationFunction,
+    ) -> Array2<f32> {
+        activation.backward_batch(z, &(output - target))
+    }
+}
+
+/// Squared error averaged over the outputs, with gradient `2 * (output - target) / n` for `n`
+/// outputs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MeanSquaredError;
+
+impl Loss for MeanSquaredError {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32 {
+        (output - target).mapv(|e| e * e).sum() / output.len() as f32
+    }
+
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32> {
+        (output - target) * (2.0 / output.len() as f32)
+    }
+
+    fn batch_value(&self, output: &Array2<f32>, target: &Array2<f32>) -> f32 {
+        (output - target).mapv(|e| e * e).sum() / output.ncols() as f32
+    }
+
+    fn batch_output_delta(
+        &self,
+        z: &Array2<f32>,
+        output: &Array2<f32>,
+        target: &Array2<f32>,
+        activation: ActivationFunction,
+    ) -> Array2<f32> {
+        let gradient = (output - target) * (2.0 / output.ncols() as f32);
+        activation.backward_batch(z, &gradient)
+    }
+}
+
+/// Per-output binary cross-entropy, averaged over the outputs. Meant for sigmoid outputs
+/// where every neuron is an independent yes/no decision.
+#[derive(Clone, Debug, Default)]
+pub struct BinaryCrossEntropy {
+    /// Multiplies the loss of positive targets, e.g. `negatives / positives` for rare events.
+    pub positive_weight: Option<f32>,
+}
+
+impl Loss for BinaryCrossEntropy {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32 {
+        let positive_weight = self.positive_weight.unwrap_or(1.0);
+        let total: f32 = output
+            .iter()
+            .zip(target)
+            .map(|(&a, &t)| {
+                let a = a.clamp(PROBABILITY_EPSILON, 1.0 - PROBABILITY_EPSILON);
+                -(positive_weight * t * a.ln() + (1.0 - t) * (1.0 - a).ln())
+            })
+            .sum();
+        total / output.len() as f32
+    }
+
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32> {
+        let positive_weight = self.positive_weight.unwrap_or(1.0);
+        let n = output.len() as f32;
+        Array1::from_iter(output.iter().zip(target).map(|(&a, &t)| {
+            let a = a.clamp(PROBABILITY_EPSILON, 1.0 - PROBABILITY_EPSILON);
+            (-positive_weight * t / a + (1.0 - t) / (1.0 - a)) / n
+        }))
+    }
+
+    fn output_delta(
+        &self,
+        z: &Array1<f32>,
+        output: &Array1<f32>,
+        target: &Array1<f32>,
+        activation: ActivationFunction,
+    ) -> Array1<f32> {
+        match activation {
+            // The sigmoid derivative cancels the denominators of the gradient.
+            ActivationFunction::Sigmoid => {
+                let positive_weight = self.positive_weight.unwrap_or(1.0);
+                let n = output.len() as f32;
+                Array1::from_iter(output.iter().zip(target).map(|(&a, &t)| {
+                    (a * (positive_weight * t + 1.0 - t) - positive_weight * t) / n
+                }))
+            }
+            _ => activation.backward(z, &self.gradient(output, target)),
+        }
+    }
+}
+
+/// Cross-entropy over one-hot (or probability) targets, meant for a
+/// [`ActivationFunction::Softmax`] output layer.
+#[derive(Clone, Debug, Default)]
+pub struct CategoricalCrossEntropy {
+    /// Per-class multipliers, e.g. inverse class frequencies for imbalanced datasets.
+    pub class_weights: Option<Array1<f32>>,
+}
+
+impl CategoricalCrossEntropy {
+    fn weighted_targets(&self, target: &Array1<f32>) -> Array1<f32> {
+        match &self.class_weights {
+            Some(weights) => target * weights,
+            None => target.clone(),
+        }
+    }
+}
+
+impl Loss for CategoricalCrossEntropy {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32 {
+        -self
+            .weighted_targets(target)
+            .iter()
+            .zip(output)
+            .map(|(&t, &a)| t * a.max(PROBABILITY_EPSILON).ln())
+            .sum::<f32>()
+    }
+
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32> {
+        Array1::from_iter(
+            self.weighted_targets(target)
+                .iter()
+                .zip(output)
+                .map(|(&t, &a)| -t / a.max(PROBABILITY_EPSILON)),
+        )
+    }
+
+    fn output_delta(
+        &self,
+        z: &Array1<f32>,
+        output: &Array1<f32>,
+        target: &Array1<f32>,
+        activation: ActivationFunction,
+    ) -> Array1<f32> {
+        match activation {
+            // Softmax followed by cross-entropy collapses to `a * sum(t) - t` (`a - t` for
+            // one-hot targets), which also avoids dividing by vanishing probabilities.
+            ActivationFunction::Softmax => {
+                let weighted = self.weighted_targets(target);
+                output * weighted.sum() - weighted
+            }
+            _ => activation.backward(z, &self.gradient(output, target)),
+        }
+    }
+
+    fn batch_output_delta(
+        &self,
+        z: &Array2<f32>,
+        output: &Array2<f32>,
+        target: &Array2<f32>,
+        activation: ActivationFunction,
+    ) -> Array2<f32> {
+        if activation != ActivationFunction::Softmax {
+            return output_delta_rows(self, z, output, target, activation);
+        }
+
+        let weighted = match &self.class_weights {
+            Some(weights) => target * weights,
+            None => target.clone(),
+        };
+        output * &weighted.sum_axis(Axis(1)).insert_axis(Axis(1)) - weighted
+    }
+}
+
+/// Squared error for small residuals and absolute error beyond `delta`, averaged over the
+/// outputs.
+#[derive(Clone, Copy, Debug)]
+pub struct Huber {
+    pub delta: f32,
+}
+
+impl Default for Huber {
+    fn default() -> Self {
+        Huber { delta: 1.0 }
+    }
+}
+
+impl Loss for Huber {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32 {
+        let total: f32 = (output - target)
+            .iter()
+            .map(|&e| {
+                if e.abs() <= self.delta {
+                    0.5 * e * e
+                } else {
+                    self.delta * (e.abs() - 0.5 * self.delta)
+                }
+            })
+            .sum();
+        total / output.len() as f32
+    }
+
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32> {
+        (output - target).mapv(|e| e.clamp(-self.delta, self.delta)) / output.len() as f32
+    }
+}
+
+/// Categorical focal loss, `-alpha_c * (1 - p_c)^gamma * ln(p_c)`. Down-weights classes the
+/// network already gets right so rare classes keep contributing to the gradient.
+#[derive(Clone, Debug)]
+pub struct FocalLoss {
+    pub gamma: f32,
+    /// Per-class `alpha` factors; every class uses 1.0 when unset.
+    pub class_weights: Option<Array1<f32>>,
+}
+
+impl Default for FocalLoss {
+    fn default() -> Self {
+        FocalLoss {
+            gamma: 2.0,
+            class_weights: None,
+        }
+    }
+}
+
+impl FocalLoss {
+    fn weight(&self, class: usize) -> f32 {
+        self.class_weights.as_ref().map_or(1.0, |w| w[class])
+    }
+}
+
+impl Loss for FocalLoss {
+    fn value(&self, output: &Array1<f32>, target: &Array1<f32>) -> f32 {
+        -output
+            .iter()
+            .zip(target)
+            .enumerate()
+            .map(|(class, (&p, &t))| {
+                let p = p.clamp(PROBABILITY_EPSILON, 1.0 - PROBABILITY_EPSILON);
+                self.weight(class) * t * (1.0 - p).powf(self.gamma) * p.ln()
+            })
+            .sum::<f32>()
+    }
+
+    fn gradient(&self, output: &Array1<f32>, target: &Array1<f32>) -> Array1<f32> {
+        Array1::from_iter(
+            output
+                .iter()
+                .zip(target)
+                .enumerate()
+                .map(|(class, (&p, &t))| {
+                    let p = p.clamp(PROBABILITY_EPSILON, 1.0 - PROBABILITY_EPSILON);
+                    let modulation = (1.0 - p).powf(self.gamma);
+                    let modulation_grad = if self.gamma == 0.0 {
+                        0.0
+                    } else {
+                        self.gamma * (1.0 - p).powf(self.gamma - 1.0)
+                    };
+                    self.weight(class) * t * (modulation_grad * p.ln() - modulation / p)
+                }),
+        )
+    }
+}
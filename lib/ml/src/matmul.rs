@@ -0,0 +1,63 @@
+use crate::nd::{Array2, ArrayView2, ArrayViewMut2};
+
+/// Rows of `a` that share one pass over `b`.
+const ROW_BLOCK: usize = 8;
+
+/// `a · b` with a summation order fixed in the code: every element adds its products one after
+/// another along `k`, with a separate multiply and add for each. The SIMD kernels behind
+/// ndarray's `dot` are picked for the CPU at runtime and may fuse or regroup them, so this is
+/// what keeps training bit-identical across machines.
+pub(crate) fn matmul(a: ArrayView2<f32>, b: ArrayView2<f32>) -> Array2<f32> {
+    let mut c = Array2::zeros((a.nrows(), b.ncols()));
+    matmul_add(a, b, c.view_mut());
+    c
+}
+
+/// Adds `a · b` to `c` in the order of [`matmul`], each product straight onto the running
+/// value of `c`. Gradients accumulate this way without a product matrix per call.
+pub(crate) fn matmul_add(a: ArrayView2<f32>, b: ArrayView2<f32>, mut c: ArrayViewMut2<f32>) {
+    let (m, k) = a.dim();
+    let (b_rows, n) = b.dim();
+    assert_eq!(k, b_rows, "matmul: inner dimensions differ");
+    assert_eq!(c.dim(), (m, n), "matmul: output has the wrong shape");
+    if m == 0 || n == 0 {
+        return;
+    }
+
+    let b = b.as_standard_layout();
+    let b = b.as_slice().expect("standard layout");
+    let c_data = c.as_slice_mut().expect("output in standard layout");
+
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx") {
+        // SAFETY: the CPU supports AVX.
+        unsafe { accumulate_avx(&a, b, c_data, n) };
+        return;
+    }
+    accumulate(&a, b, c_data, n);
+}
+
+/// Adds `a · b` to `c`, both row-major with `n` columns. Only whole rows of `c` are
+/// vectorized, which leaves the order along `k` untouched.
+#[inline(always)]
+fn accumulate(a: &ArrayView2<f32>, b: &[f32], c: &mut [f32], n: usize) {
+    for (block, c_block) in c.chunks_mut(ROW_BLOCK * n).enumerate() {
+        let first = block * ROW_BLOCK;
+        for (kk, b_row) in b.chunks_exact(n).enumerate() {
+            for (r, c_row) in c_block.chunks_exact_mut(n).enumerate() {
+                let scale = a[[first + r, kk]];
+                for (c, &b) in c_row.iter_mut().zip(b_row) {
+                    *c += scale * b;
+                }
+            }
+        }
+    }
+}
+
+/// [`accumulate`] with wider vectors. Separate multiplies and adds round the same at any
+/// width, and Rust never fuses them, so the result is the same bit for bit.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn accumulate_avx(a: &ArrayView2<f32>, b: &[f32], c: &mut [f32], n: usize) {
+    accumulate(a, b, c, n)
+}
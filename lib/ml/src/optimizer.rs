@@ -0,0 +1,293 @@
+use crate::nd::{ArrayD, ArrayViewD, ArrayViewMutD, Zip};
+use ray_shared::result::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Internal state of an optimizer, stored in training checkpoints.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct OptimizerState {
+    /// Optimizer that wrote the state; loading it into another kind fails.
+    pub kind: String,
+    pub step: u64,
+    /// Per-slot buffers, one group per kind of state (velocities, moments, ...).
+    pub buffers: Vec<Vec<ArrayD<f32>>>,
+}
+
+impl OptimizerState {
+    fn into_buffers<const N: usize>(self, kind: &str) -> Result<[Vec<ArrayD<f32>>; N]> {
+        if self.kind != kind {
+            bail!(
+                "Optimizer state was written by {}, it cannot be loaded into {kind}",
+                self.kind
+            );
+        }
+        let groups = self.buffers.len();
+        match self.buffers.try_into() {
+            Ok(buffers) => Ok(buffers),
+            Err(_) => bail!("Optimizer state holds {groups} buffer groups, expected {N}"),
+        }
+    }
+}
+
+/// Turns gradients into parameter updates. Every trainable tensor of the network is identified
+/// by a `slot` that stays the same between steps, so implementations can keep per-parameter
+/// state such as velocities or moment estimates.
+pub trait Optimizer: Send + Sync {
+    /// Called once per update, before the `update` calls for that step.
+    fn begin_step(&mut self) {}
+
+    fn update(
+        &mut self,
+        slot: usize,
+        param: ArrayViewMutD<f32>,
+        grad: ArrayViewD<f32>,
+        learning_rate: f32,
+    );
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState::default()
+    }
+
+    fn load_state(&mut self, _state: OptimizerState) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Per-slot state buffer, created with the parameter's shape on first use.
+fn slot_state<'a>(
+    states: &'a mut Vec<ArrayD<f32>>,
+    slot: usize,
+    grad: &ArrayViewD<f32>,
+) -> &'a mut ArrayD<f32> {
+    if states.len() <= slot {
+        states.resize(slot + 1, ArrayD::zeros(vec![0]));
+    }
+    if states[slot].shape() != grad.shape() {
+        states[slot] = ArrayD::zeros(grad.raw_dim());
+    }
+    &mut states[slot]
+}
+
+/// Stochastic gradient descent with (optionally Nesterov) momentum.
+#[derive(Clone, Debug, Default)]
+pub struct Sgd {
+    pub momentum: f32,
+    pub nesterov: bool,
+    velocities: Vec<ArrayD<f32>>,
+}
+
+impl Sgd {
+    pub fn new(momentum: f32) -> Self {
+        Sgd {
+            momentum,
+            nesterov: false,
+            velocities: Vec::new(),
+        }
+    }
+
+    pub fn nesterov(momentum: f32) -> Self {
+        Sgd {
+            nesterov: true,
+            ..Sgd::new(momentum)
+        }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn update(
+        &mut self,
+        slot: usize,
+        mut param: ArrayViewMutD<f32>,
+        grad: ArrayViewD<f32>,
+        learning_rate: f32,
+    ) {
+        let momentum = self.momentum;
+        let nesterov = self.nesterov;
+        let velocity = slot_state(&mut self.velocities, slot, &grad);
+
+        Zip::from(&mut param)
+            .and(velocity)
+            .and(&grad)
+            .for_each(|w, v, &g| {
+                *v = *v * momentum - learning_rate * g;
+                if nesterov {
+                    // Step from the look-ahead position the velocity is about to reach.
+                    *w += momentum * *v - learning_rate * g;
+                } else {
+                    *w += *v;
+                }
+            });
+    }
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState {
+            kind: "Sgd".to_string(),
+            step: 0,
+            buffers: vec![self.velocities.clone()],
+        }
+    }
+
+    fn load_state(&mut self, state: OptimizerState) -> Result<()> {
+        [self.velocities] = state.into_buffers("Sgd")?;
+        Ok(())
+    }
+}
+
+/// Adam, optionally with decoupled weight decay (AdamW).
+#[derive(Clone, Debug)]
+pub struct Adam {
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    /// Decoupled weight decay applied to matrices and kernels; biases and other vectors are
+    /// left alone. Zero gives plain Adam.
+    pub weight_decay: f32,
+    step: i32,
+    first_moments: Vec<ArrayD<f32>>,
+    second_moments: Vec<ArrayD<f32>>,
+}
+
+impl Default for Adam {
+    fn default() -> Self {
+        Adam {
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            weight_decay: 0.0,
+            step: 0,
+            first_moments: Vec::new(),
+            second_moments: Vec::new(),
+        }
+    }
+}
+
+impl Adam {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// AdamW: Adam with weight decay applied directly to the weights instead of being mixed
+    /// into the gradient.
+    pub fn with_weight_decay(weight_decay: f32) -> Self {
+        Adam {
+            weight_decay,
+            ..Self::default()
+        }
+    }
+}
+
+impl Optimizer for Adam {
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(
+        &mut self,
+        slot: usize,
+        mut param: ArrayViewMutD<f32>,
+        grad: ArrayViewD<f32>,
+        learning_rate: f32,
+    ) {
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+        let step = self.step.max(1);
+        let correction1 = 1.0 - beta1.powi(step);
+        let correction2 = 1.0 - beta2.powi(step);
+        let decay = if param.ndim() > 1 {
+            learning_rate * self.weight_decay
+        } else {
+            0.0
+        };
+
+        let m = slot_state(&mut self.first_moments, slot, &grad);
+        Zip::from(&mut *m)
+            .and(&grad)
+            .for_each(|m, &g| *m = beta1 * *m + (1.0 - beta1) * g);
+
+        let v = slot_state(&mut self.second_moments, slot, &grad);
+        Zip::from(&mut *v)
+            .and(&grad)
+            .for_each(|v, &g| *v = beta2 * *v + (1.0 - beta2) * g * g);
+
+        let m = &self.first_moments[slot];
+        Zip::from(&mut param)
+            .and(m)
+            .and(&self.second_moments[slot])
+            .for_each(|w, &m, &v| {
+                let m_hat = m / correction1;
+                let v_hat = v / correction2;
+                *w -= decay * *w + learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+            });
+    }
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState {
+            kind: "Adam".to_string(),
+            step: self.step as u64,
+            buffers: vec![self.first_moments.clone(), self.second_moments.clone()],
+        }
+    }
+
+    fn load_state(&mut self, state: OptimizerState) -> Result<()> {
+        let step = state.step as i32;
+        [self.first_moments, self.second_moments] = state.into_buffers("Adam")?;
+        self.step = step;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RmsProp {
+    /// Decay rate of the squared gradient average.
+    pub rho: f32,
+    pub epsilon: f32,
+    mean_squares: Vec<ArrayD<f32>>,
+}
+
+impl Default for RmsProp {
+    fn default() -> Self {
+        RmsProp {
+            rho: 0.9,
+            epsilon: 1e-8,
+            mean_squares: Vec::new(),
+        }
+    }
+}
+
+impl RmsProp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn update(
+        &mut self,
+        slot: usize,
+        mut param: ArrayViewMutD<f32>,
+        grad: ArrayViewD<f32>,
+        learning_rate: f32,
+    ) {
+        let (rho, epsilon) = (self.rho, self.epsilon);
+        let mean_square = slot_state(&mut self.mean_squares, slot, &grad);
+
+        Zip::from(&mut param)
+            .and(mean_square)
+            .and(&grad)
+            .for_each(|w, s, &g| {
+                *s = rho * *s + (1.0 - rho) * g * g;
+                *w -= learning_rate * g / (s.sqrt() + epsilon);
+            });
+    }
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState {
+            kind: "RmsProp".to_string(),
+            step: 0,
+            buffers: vec![self.mean_squares.clone()],
+        }
+    }
+
+    fn load_state(&mut self, state: OptimizerState) -> Result<()> {
+        [self.mean_squares] = state.into_buffers("RmsProp")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use crate::nd::{s, Array1, Array2, ArrayD, ArrayView1, ArrayView2, ArrayViewMut1};
+use crate::{
+    argmax, ActivationRange, Layer, LayerCache, QuantizationConfig, QuantizationParams,
+    QuantizedLayer,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub enum Pooling {
+    Max,
+    Average,
+}
+
+/// Pools windows of `size` values of every channel, `stride` apart. Like [`crate::Conv1d`],
+/// samples are `channels` sequences of `input_length` values stored one after the other.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Pool1d {
+    pub pooling: Pooling,
+    pub channels: usize,
+    pub input_length: usize,
+    pub size: usize,
+    pub stride: usize,
+}
+
+impl Pool1d {
+    /// Non-overlapping windows: the stride equals the window size.
+    pub fn new(pooling: Pooling, channels: usize, input_length: usize, size: usize) -> Self {
+        assert!(
+            size > 0 && size <= input_length,
+            "The pooling window must fit into the input."
+        );
+        Pool1d {
+            pooling,
+            channels,
+            input_length,
+            size,
+            stride: size,
+        }
+    }
+
+    /// Averages every channel down to a single value.
+    pub fn global_average(channels: usize, input_length: usize) -> Self {
+        Self::new(Pooling::Average, channels, input_length, input_length)
+    }
+
+    pub fn output_length(&self) -> usize {
+        (self.input_length - self.size) / self.stride + 1
+    }
+
+    /// Input indices pooled into every output index of a sample.
+    fn windows(&self) -> impl Iterator<Item = (usize, std::ops::Range<usize>)> + '_ {
+        let length = self.output_length();
+        (0..self.channels * length).map(move |output| {
+            let (channel, position) = (output / length, output % length);
+            let start = channel * self.input_length + position * self.stride;
+            (output, start..start + self.size)
+        })
+    }
+
+    fn pool_sample(&self, input: ArrayView1<f32>, mut output: ArrayViewMut1<f32>) {
+        for (index, window) in self.windows() {
+            let values = input.slice(s![window]);
+            output[index] = match self.pooling {
+                Pooling::Max => values[argmax(values.iter())],
+                Pooling::Average => values.sum() / self.size as f32,
+            };
+        }
+    }
+}
+
+impl Layer for Pool1d {
+    fn input_size(&self) -> usize {
+        self.channels * self.input_length
+    }
+
+    fn output_size(&self) -> usize {
+        self.channels * self.output_length()
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        let mut output = Array2::zeros((input.nrows(), self.output_size()));
+        for (input, output) in input.rows().into_iter().zip(output.rows_mut()) {
+            self.pool_sample(input, output);
+        }
+        output
+    }
+
+    /// Max pooling finds the largest values again from `input` instead of caching them.
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        _cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        _parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        input_grad.then(|| {
+            let mut grad = Array2::zeros(input.raw_dim());
+            for ((input, output_grad), mut grad) in input
+                .rows()
+                .into_iter()
+                .zip(output_grad.rows())
+                .zip(grad.rows_mut())
+            {
+                for (index, window) in self.windows() {
+                    match self.pooling {
+                        Pooling::Max => {
+                            // Ties send the gradient to the first largest value.
+                            let max = argmax(input.slice(s![window.clone()]).iter());
+                            grad[window.start + max] += output_grad[index];
+                        }
+                        Pooling::Average => {
+                            let share = output_grad[index] / self.size as f32;
+                            grad.slice_mut(s![window]).mapv_inplace(|g| g + share);
+                        }
+                    }
+                }
+            }
+            grad
+        })
+    }
+
+    /// Pooling keeps the input grid; the calibrated output range is not needed.
+    fn quantize(
+        &self,
+        input: QuantizationParams,
+        _output: ActivationRange,
+        _config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        Some(QuantizedLayer::Pool1d(QuantizedPool1d {
+            pool: *self,
+            params: input,
+        }))
+    }
+}
+
+/// Integer counterpart of [`Pool1d`]. Input and output share `params`, so the maximum is
+/// exact and the average is rounded half away from zero like [`QuantizationParams::quantize`]
+/// rounds.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QuantizedPool1d {
+    pub pool: Pool1d,
+    pub params: QuantizationParams,
+}
+
+impl QuantizedPool1d {
+    pub fn feedforward(&self, input: &Array1<i8>) -> Array1<i8> {
+        let mut output = Array1::zeros(self.pool.output_size());
+        for (index, window) in self.pool.windows() {
+            let values = input.slice(s![window]);
+            output[index] = match self.pool.pooling {
+                Pooling::Max => values[argmax(values.iter())],
+                Pooling::Average => {
+                    // Round relative to the zero point, which is where `quantize` rounds.
+                    let size = self.pool.size as i32;
+                    let sum: i32 = values
+                        .iter()
+                        .map(|&q| q as i32 - self.params.zero_point)
+                        .sum();
+                    (divide_rounded(sum, size) + self.params.zero_point) as i8
+                }
+            };
+        }
+        output
+    }
+}
+
+/// `numerator / denominator` rounded half away from zero, like `f32::round`.
+fn divide_rounded(numerator: i32, denominator: i32) -> i32 {
+    let half = denominator / 2;
+    if numerator >= 0 {
+        (numerator + half) / denominator
+    } else {
+        (numerator - half) / denominator
+    }
+}
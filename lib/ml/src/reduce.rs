@@ -0,0 +1,91 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Most accumulators of a deterministic [`Reduction::accumulate`]. It bounds the parallelism,
+/// and changing it changes the rounding of every seeded run.
+const LANES: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum Reduction {
+    /// Sums the items in a balanced binary tree. The grouping only depends on the number of
+    /// items and the layers multiply matrices in a fixed order, so results are bit-identical
+    /// for any thread count and CPU, given the same math library for `exp` and `tanh`.
+    #[default]
+    Deterministic,
+    /// rayon's work-stealing reduce; the summation order follows the thread scheduling.
+    Unordered,
+}
+
+impl Reduction {
+    /// Combines `map(i)` for every `i` in `0..len`. `identity` must be neutral for `combine`.
+    /// Every item is its own task, so callers group cheap items into chunks first.
+    pub(crate) fn reduce<T, M, I, C>(&self, len: usize, map: M, identity: I, combine: C) -> T
+    where
+        T: Send,
+        M: Fn(usize) -> T + Sync,
+        I: Fn() -> T + Sync,
+        C: Fn(T, T) -> T + Sync,
+    {
+        match self {
+            Reduction::Deterministic => tree_reduce(0, len, &map, &identity, &combine),
+            Reduction::Unordered => (0..len)
+                .into_par_iter()
+                .map(&map)
+                .reduce(&identity, &combine),
+        }
+    }
+
+    /// Like [`Reduction::reduce`], but `add(&mut acc, i)` adds item `i` to an accumulator
+    /// that is reused for many items, so items need no result of their own. The deterministic
+    /// mode gives up to [`LANES`] contiguous runs of items an accumulator each and combines
+    /// them in a tree; the unordered mode keeps one per rayon task.
+    pub(crate) fn accumulate<T, I, A, C>(&self, len: usize, identity: I, add: A, combine: C) -> T
+    where
+        T: Send,
+        I: Fn() -> T + Sync + Send,
+        A: Fn(&mut T, usize) + Sync + Send,
+        C: Fn(T, T) -> T + Sync + Send,
+    {
+        match self {
+            Reduction::Deterministic => {
+                let lanes = len.min(LANES);
+                let lane = |lane: usize| {
+                    let mut acc = identity();
+                    for i in lane * len / lanes..(lane + 1) * len / lanes {
+                        add(&mut acc, i);
+                    }
+                    acc
+                };
+                tree_reduce(0, lanes, &lane, &identity, &combine)
+            }
+            Reduction::Unordered => (0..len)
+                .into_par_iter()
+                .fold(&identity, |mut acc, i| {
+                    add(&mut acc, i);
+                    acc
+                })
+                .reduce(&identity, &combine),
+        }
+    }
+}
+
+fn tree_reduce<T, M, I, C>(start: usize, end: usize, map: &M, identity: &I, combine: &C) -> T
+where
+    T: Send,
+    M: Fn(usize) -> T + Sync,
+    I: Fn() -> T + Sync,
+    C: Fn(T, T) -> T + Sync,
+{
+    match end - start {
+        0 => return identity(),
+        1 => return map(start),
+        _ => {}
+    }
+
+    let mid = start + (end - start) / 2;
+    let (left, right) = rayon::join(
+        || tree_reduce(start, mid, map, identity, combine),
+        || tree_reduce(mid, end, map, identity, combine),
+    );
+    combine(left, right)
+}
@@ -0,0 +1,38 @@
+use crate::nd::{ArrayD, ArrayViewD};
+use serde::{Deserialize, Serialize};
+
+/// Weight penalty `l1 * sum(|w|) + l2 * sum(w^2)` added to the loss of a layer's weights.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub struct Regularization {
+    pub l1: f32,
+    pub l2: f32,
+}
+
+impl Regularization {
+    pub fn l1(l1: f32) -> Self {
+        Regularization { l1, l2: 0.0 }
+    }
+
+    pub fn l2(l2: f32) -> Self {
+        Regularization { l1: 0.0, l2 }
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.l1 == 0.0 && self.l2 == 0.0
+    }
+
+    pub fn penalty(&self, weights: &ArrayViewD<f32>) -> f32 {
+        weights
+            .iter()
+            .map(|&w| self.l1 * w.abs() + self.l2 * w * w)
+            .sum()
+    }
+
+    /// Gradient of [`Regularization::penalty`]; the L1 term counts as `0` at `w = 0`.
+    pub fn gradient(&self, weights: &ArrayViewD<f32>) -> ArrayD<f32> {
+        weights.mapv(|w| {
+            let sign = if w == 0.0 { 0.0 } else { w.signum() };
+            self.l1 * sign + 2.0 * self.l2 * w
+        })
+    }
+}
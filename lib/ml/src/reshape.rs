@@ -0,0 +1,68 @@
+use crate::nd::{Array2, ArrayD, ArrayView2};
+use crate::{
+    ActivationRange, Layer, LayerCache, QuantizationConfig, QuantizationParams, QuantizedLayer,
+};
+use serde::{Deserialize, Serialize};
+
+/// Reinterprets the row-major values of a sample as another shape with the same number of
+/// elements. Rows are stored flat either way, so only the declared shapes change.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Reshape {
+    pub input_shape: Vec<usize>,
+    pub output_shape: Vec<usize>,
+}
+
+impl Reshape {
+    pub fn new(input_shape: Vec<usize>, output_shape: Vec<usize>) -> Self {
+        assert_eq!(
+            input_shape.iter().product::<usize>(),
+            output_shape.iter().product::<usize>(),
+            "Reshaping must keep the number of elements."
+        );
+        Reshape {
+            input_shape,
+            output_shape,
+        }
+    }
+
+    /// Collapses `input_shape` into one dimension.
+    pub fn flatten(input_shape: Vec<usize>) -> Self {
+        let size = input_shape.iter().product();
+        Self::new(input_shape, vec![size])
+    }
+}
+
+impl Layer for Reshape {
+    fn input_size(&self) -> usize {
+        self.input_shape.iter().product()
+    }
+
+    fn output_size(&self) -> usize {
+        self.output_shape.iter().product()
+    }
+
+    fn forward(&self, input: ArrayView2<f32>) -> Array2<f32> {
+        input.to_owned()
+    }
+
+    fn backward(
+        &self,
+        _input: &Array2<f32>,
+        _cache: &LayerCache,
+        output_grad: &Array2<f32>,
+        _parameter_grads: &mut [ArrayD<f32>],
+        input_grad: bool,
+    ) -> Option<Array2<f32>> {
+        input_grad.then(|| output_grad.clone())
+    }
+
+    /// The integer network stores samples flat as well, so nothing is left to do there.
+    fn quantize(
+        &self,
+        _input: QuantizationParams,
+        _output: ActivationRange,
+        _config: &QuantizationConfig,
+    ) -> Option<QuantizedLayer> {
+        None
+    }
+}
@@ -0,0 +1,203 @@
+use ray_shared::result::{bail, Result};
+use std::f32::consts::PI;
+
+/// Scales the trainer's base learning rate over the course of training. `epoch` counts
+/// completed epochs and `step` counts optimizer updates, both across `train` and `fine_tune`.
+pub trait LearningRateSchedule: Send + Sync {
+    fn learning_rate(&self, base: f32, epoch: usize, step: usize) -> f32;
+
+    /// Called with the validation loss after every validation, or with the training loss
+    /// after every epoch when the trainer has no validation set.
+    fn end_epoch(&mut self, _loss: f32) {}
+
+    /// State carried between epochs, stored in training checkpoints. Schedules that only
+    /// depend on `epoch` and `step` have none.
+    fn state(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn load_state(&mut self, _state: &[f64]) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Constant;
+
+impl LearningRateSchedule for Constant {
+    fn learning_rate(&self, base: f32, _epoch: usize, _step: usize) -> f32 {
+        base
+    }
+}
+
+/// Multiplies the rate by `gamma` every `step_size` epochs.
+#[derive(Clone, Copy, Debug)]
+pub struct StepDecay {
+    pub step_size: usize,
+    pub gamma: f32,
+}
+
+impl LearningRateSchedule for StepDecay {
+    fn learning_rate(&self, base: f32, epoch: usize, _step: usize) -> f32 {
+        base * self.gamma.powi((epoch / self.step_size.max(1)) as i32)
+    }
+}
+
+/// Multiplies the rate by `gamma` every epoch.
+#[derive(Clone, Copy, Debug)]
+pub struct Exponential {
+    pub gamma: f32,
+}
+
+impl LearningRateSchedule for Exponential {
+    fn learning_rate(&self, base: f32, epoch: usize, _step: usize) -> f32 {
+        base * self.gamma.powi(epoch as i32)
+    }
+}
+
+/// SGDR: anneals from the base rate down to `min_learning_rate` along a cosine over `period`
+/// epochs, then restarts with a period `period_mult` times as long.
+#[derive(Clone, Copy, Debug)]
+pub struct CosineAnnealing {
+    pub period: usize,
+    pub period_mult: usize,
+    pub min_learning_rate: f32,
+}
+
+impl CosineAnnealing {
+    pub fn new(period: usize, min_learning_rate: f32) -> Self {
+        CosineAnnealing {
+            period,
+            period_mult: 1,
+            min_learning_rate,
+        }
+    }
+}
+
+impl LearningRateSchedule for CosineAnnealing {
+    fn learning_rate(&self, base: f32, epoch: usize, _step: usize) -> f32 {
+        let mut period = self.period.max(1);
+        let mut position = epoch;
+        while position >= period {
+            position -= period;
+            period *= self.period_mult.max(1);
+        }
+
+        let progress = position as f32 / period as f32;
+        self.min_learning_rate
+            + 0.5 * (base - self.min_learning_rate) * (1.0 + (PI * progress).cos())
+    }
+}
+
+/// Ramps the rate linearly from zero over the first `steps` updates, then hands over to
+/// `schedule`.
+pub struct LinearWarmup {
+    pub steps: usize,
+    pub schedule: Box<dyn LearningRateSchedule>,
+}
+
+impl LinearWarmup {
+    pub fn new(steps: usize, schedule: impl LearningRateSchedule + 'static) -> Self {
+        LinearWarmup {
+            steps,
+            schedule: Box::new(schedule),
+        }
+    }
+}
+
+impl LearningRateSchedule for LinearWarmup {
+    fn learning_rate(&self, base: f32, epoch: usize, step: usize) -> f32 {
+        let rate = self.schedule.learning_rate(base, epoch, step);
+        if step < self.steps {
+            rate * (step + 1) as f32 / self.steps as f32
+        } else {
+            rate
+        }
+    }
+
+    fn end_epoch(&mut self, loss: f32) {
+        self.schedule.end_epoch(loss);
+    }
+
+    fn state(&self) -> Vec<f64> {
+        self.schedule.state()
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> Result<()> {
+        self.schedule.load_state(state)
+    }
+}
+
+/// Multiplies the rate by `factor` once the loss has not improved by more than `threshold`
+/// (relative) for `patience` epochs.
+#[derive(Clone, Copy, Debug)]
+pub struct ReduceOnPlateau {
+    pub factor: f32,
+    pub patience: usize,
+    pub threshold: f32,
+    pub min_learning_rate: f32,
+    best: f32,
+    bad_epochs: usize,
+    scale: f32,
+}
+
+impl Default for ReduceOnPlateau {
+    fn default() -> Self {
+        ReduceOnPlateau {
+            factor: 0.1,
+            patience: 10,
+            threshold: 1e-4,
+            min_learning_rate: 0.0,
+            best: f32::INFINITY,
+            bad_epochs: 0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ReduceOnPlateau {
+    pub fn new(factor: f32, patience: usize) -> Self {
+        ReduceOnPlateau {
+            factor,
+            patience,
+            ..Self::default()
+        }
+    }
+}
+
+impl LearningRateSchedule for ReduceOnPlateau {
+    fn learning_rate(&self, base: f32, _epoch: usize, _step: usize) -> f32 {
+        (base * self.scale).max(self.min_learning_rate)
+    }
+
+    fn end_epoch(&mut self, loss: f32) {
+        if loss < self.best * (1.0 - self.threshold) {
+            self.best = loss;
+            self.bad_epochs = 0;
+            return;
+        }
+
+        self.bad_epochs += 1;
+        if self.bad_epochs > self.patience {
+            self.scale *= self.factor;
+            self.bad_epochs = 0;
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        vec![self.best as f64, self.bad_epochs as f64, self.scale as f64]
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> Result<()> {
+        let &[best, bad_epochs, scale] = state else {
+            bail!(
+                "ReduceOnPlateau expects 3 state values, got {}",
+                state.len()
+            );
+        };
+        self.best = best as f32;
+        self.bad_epochs = bad_epochs as usize;
+        self.scale = scale as f32;
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{sigmoid, QuantizationParams};
+use serde::{Deserialize, Serialize};
+
+/// Fractional bits of the LUT input: `x >> FRACTION_BITS` is the interval `x` falls into.
+const FRACTION_BITS: u32 = 7;
+
+/// Layout of the integer sigmoid, generated from the float function. Sigmoid layers
+/// requantize their accumulator onto [`SigmoidLut::input_scale`], so the table entry is found
+/// with a shift, which is how the RaySoC RTL does it too.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SigmoidLut {
+    /// Table entries, odd so the middle one sits at zero. Inputs past the ends saturate.
+    pub entries: usize,
+    /// Pre-activation distance between neighbouring entries.
+    pub step: f32,
+    /// Interpolate linearly between the two entries around the input instead of returning
+    /// the entry of its interval.
+    pub interpolate: bool,
+}
+
+impl Default for SigmoidLut {
+    /// 17 entries covering `[-8, 8]` without interpolation, the layout of the original
+    /// hand-written table.
+    fn default() -> Self {
+        SigmoidLut {
+            entries: 17,
+            step: 1.0,
+            interpolate: false,
+        }
+    }
+}
+
+impl SigmoidLut {
+    /// Scale of the requantized pre-activations the table is indexed with.
+    pub fn input_scale(&self) -> f32 {
+        self.step / (1 << FRACTION_BITS) as f32
+    }
+
+    fn half(&self) -> i32 {
+        assert!(
+            self.entries >= 3 && self.entries % 2 == 1,
+            "A sigmoid LUT needs an odd number of at least 3 entries."
+        );
+        (self.entries / 2) as i32
+    }
+
+    /// The table in `output` quantization. Entry `i` holds the sigmoid at `(i - entries / 2) *
+    /// step`, or without interpolation in the middle of the interval starting there.
+    pub fn table(&self, output: QuantizationParams) -> Vec<i8> {
+        let offset = if self.interpolate { 0.0 } else { 0.5 };
+        let half = self.half();
+        (0..self.entries as i32)
+            .map(|i| output.quantize(sigmoid(((i - half) as f32 + offset) * self.step)))
+            .collect()
+    }
+
+    /// Looks up `x`, a pre-activation on the [`SigmoidLut::input_scale`] grid, in `table`.
+    pub fn lookup(&self, table: &[i8], x: i32) -> i8 {
+        let half = self.half();
+        let interval = x >> FRACTION_BITS;
+        if !self.interpolate {
+            return table[(interval.clamp(-half, half) + half) as usize];
+        }
+
+        if interval < -half {
+            return table[0];
+        }
+        if interval >= half {
+            return table[self.entries - 1];
+        }
+
+        let index = (interval + half) as usize;
+        let (low, high) = (table[index] as i32, table[index + 1] as i32);
+        let fraction = x & ((1 << FRACTION_BITS) - 1);
+        (low + (((high - low) * fraction + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS)) as i8
+    }
+}
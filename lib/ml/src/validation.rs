@@ -0,0 +1,149 @@
+use crate::nd::Array1;
+use crate::{
+    argmax, stack_rows, DataPoint, Loss, NetworkLayer, NeuralNetwork, QuantizedNeuralNetwork,
+    Reduction,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+pub struct Evaluation {
+    /// Average loss per sample.
+    pub loss: f32,
+    /// Fraction of samples whose predicted class matches the target. Single-output networks
+    /// count as binary classifiers with a 0.5 threshold.
+    pub accuracy: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Metric {
+    Loss,
+    Accuracy,
+}
+
+impl Metric {
+    pub fn value(&self, evaluation: &Evaluation) -> f32 {
+        match self {
+            Metric::Loss => evaluation.loss,
+            Metric::Accuracy => evaluation.accuracy,
+        }
+    }
+
+    /// Whether `value` beats `best` by more than `min_delta`.
+    pub fn improves(&self, value: f32, best: f32, min_delta: f32) -> bool {
+        match self {
+            Metric::Loss => value < best - min_delta,
+            Metric::Accuracy => value > best + min_delta,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EarlyStopping {
+    pub metric: Metric,
+    /// Epochs without improvement after which training stops.
+    pub patience: usize,
+    /// Smallest change of the metric that counts as an improvement.
+    pub min_delta: f32,
+    /// Put the weights of the best evaluation back into the network when training ends.
+    pub restore_best: bool,
+}
+
+impl Default for EarlyStopping {
+    fn default() -> Self {
+        EarlyStopping {
+            metric: Metric::Loss,
+            patience: 10,
+            min_delta: 0.0,
+            restore_best: true,
+        }
+    }
+}
+
+impl EarlyStopping {
+    pub fn new(metric: Metric, patience: usize) -> Self {
+        EarlyStopping {
+            metric,
+            patience,
+            ..Self::default()
+        }
+    }
+}
+
+/// Best evaluation of the running `train` or `fine_tune` call, from which [`EarlyStopping`]
+/// counts its patience.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EarlyStoppingState {
+    /// Metric value of the evaluation.
+    pub value: f32,
+    /// Epoch of the evaluation, counted like [`crate::Trainer::epoch`].
+    pub epoch: usize,
+    /// Network layers after that epoch, put back when `restore_best` is set.
+    pub layers: Vec<NetworkLayer>,
+}
+
+fn is_correct(output: &Array1<f32>, predicted: usize, target: &Array1<f32>) -> bool {
+    if target.len() == 1 {
+        (output[0] >= 0.5) == (target[0] >= 0.5)
+    } else {
+        predicted == argmax(target.iter())
+    }
+}
+
+/// Samples evaluated together, as one matrix per layer for float networks.
+const EVALUATION_CHUNK: usize = 32;
+
+/// Averages the loss sums and correct counts `chunk` returns for slices of `data`.
+fn average(data: &[DataPoint], chunk: impl Fn(&[DataPoint]) -> (f32, usize) + Sync) -> Evaluation {
+    let chunks: Vec<&[DataPoint]> = data.chunks(EVALUATION_CHUNK).collect();
+
+    // Validation decides early stopping and plateau schedules, so it has to be as
+    // reproducible as the gradients.
+    let (loss, correct) = Reduction::Deterministic.reduce(
+        chunks.len(),
+        |i| chunk(chunks[i]),
+        || (0.0, 0),
+        |a, b| (a.0 + b.0, a.1 + b.1),
+    );
+
+    let count = data.len().max(1) as f32;
+    Evaluation {
+        loss: loss / count,
+        accuracy: correct as f32 / count,
+    }
+}
+
+impl NeuralNetwork {
+    pub fn evaluate(&self, data: &[DataPoint], loss: &dyn Loss) -> Evaluation {
+        average(data, |points| {
+            let outputs = self.feedforward_batch(&stack_rows(points.iter().map(|p| &p.inputs)));
+
+            points
+                .iter()
+                .zip(outputs.rows())
+                .fold((0.0, 0), |(total, correct), (point, output)| {
+                    let output = output.to_owned();
+                    let predicted = argmax(output.iter());
+                    (
+                        total + loss.value(&output, &point.targets),
+                        correct + is_correct(&output, predicted, &point.targets) as usize,
+                    )
+                })
+        })
+    }
+}
+
+impl QuantizedNeuralNetwork {
+    /// Evaluates the integer network; the loss is taken on the dequantized outputs.
+    pub fn evaluate(&self, data: &[DataPoint], loss: &dyn Loss) -> Evaluation {
+        average(data, |points| {
+            points.iter().fold((0.0, 0), |(total, correct), point| {
+                let input = self.quantize_input(&point.inputs);
+                let output = self.dequantize_output(&self.feedforward(&input));
+                (
+                    total + loss.value(&output, &point.targets),
+                    correct + is_correct(&output, self.predict(&input), &point.targets) as usize,
+                )
+            })
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use ray_ml::nd::{arr1, Array1};
+use ray_ml::*;
+
+const INPUT: ActivationRange = ActivationRange {
+    min: -1.0,
+    max: 1.0,
+};
+
+fn range(min: f32, max: f32) -> ActivationRange {
+    ActivationRange { min, max }
+}
+
+fn configs() -> [QuantizationConfig; 2] {
+    [
+        QuantizationConfig::default(),
+        QuantizationConfig {
+            activation_scheme: QuantizationScheme::Asymmetric,
+            ..QuantizationConfig::default()
+        },
+    ]
+}
+
+/// `activation(weights * x)` for a single input in `[-1, 1]`, quantized with outputs in
+/// `output`.
+fn quantized(
+    activation: ActivationFunction,
+    weights: &[f32],
+    output: ActivationRange,
+    config: &QuantizationConfig,
+) -> QuantizedDense {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut dense = Dense::new(1, weights.len(), activation, &mut rng);
+    dense.weights.column_mut(0).assign(&arr1(weights));
+    let network = NeuralNetwork {
+        layers: vec![dense.into()],
+    };
+    let calibration = Calibration {
+        ranges: vec![INPUT, output],
+    };
+    let quantized = network.quantize_with(&calibration, config).unwrap();
+    quantized.layers[0].as_dense().unwrap().clone()
+}
+
+/// The pre-activations the integer layer sees for the accumulators `acc`: the requantized
+/// values on the grid of the activation's table or output.
+fn grid_points(layer: &QuantizedDense, acc: &Array1<i32>) -> Array1<f32> {
+    Array1::from_iter(
+        acc.iter()
+            .zip(&layer.requantizations)
+            .zip(&layer.bias_scales)
+            .map(|((&acc, requant), &bias_scale)| {
+                requant.apply(acc) as f32 * bias_scale / requant.scale()
+            }),
+    )
+}
+
+/// Feeds every int8 input and checks that the output is within one step of the float
+/// activation of the pre-activation the layer looked up, quantized the same way. Beyond the
+/// table the clamped entries have to hold as well.
+fn assert_within_one_step(layer: &QuantizedDense) {
+    for q in i8::MIN..=i8::MAX {
+        let acc = layer.accumulate(&arr1(&[q]));
+        let expected = layer
+            .activation
+            .activate(&grid_points(layer, &acc))
+            .mapv(|a| layer.output.quantize(a));
+        let actual = layer.activate(&acc);
+        for (&actual, &expected) in actual.iter().zip(&expected) {
+            assert!(
+                (actual as i32 - expected as i32).abs() <= 1,
+                "{:?} with {:?} at input {q}: {actual} != {expected}",
+                layer.activation,
+                layer.output
+            );
+        }
+    }
+}
+
+/// Table activations with pre-activations reaching past the table bound.
+#[test]
+fn table_activations() {
+    let cases = [
+        (ActivationFunction::Tanh, 6.0, range(-1.0, 1.0)),
+        (ActivationFunction::HardSigmoid, 5.0, range(0.0, 1.0)),
+        (ActivationFunction::HardSwish, 6.0, range(-0.375, 6.0)),
+        (ActivationFunction::SiLU, 10.0, range(-0.28, 10.0)),
+        (ActivationFunction::GELU, 5.0, range(-0.17, 5.0)),
+    ];
+    for config in configs() {
+        for (activation, weight, output) in cases {
+            assert_within_one_step(&quantized(activation, &[weight], output, &config));
+        }
+    }
+}
+
+/// The default table is constant over whole intervals, so only an interpolated table this
+/// fine follows the curve to within a step.
+#[test]
+fn interpolated_sigmoid() {
+    for config in configs() {
+        let config = QuantizationConfig {
+            sigmoid_lut: SigmoidLut {
+                entries: 65,
+                step: 0.25,
+                interpolate: true,
+            },
+            ..config
+        };
+        let layer = quantized(
+            ActivationFunction::Sigmoid,
+            &[10.0],
+            range(0.0, 1.0),
+            &config,
+        );
+        assert_within_one_step(&layer);
+    }
+}
+
+#[test]
+fn piecewise_linear_activations() {
+    let cases = [
+        (ActivationFunction::ReLU, 3.0, range(0.0, 3.0)),
+        (ActivationFunction::Linear, 2.0, range(-2.0, 2.0)),
+        (ActivationFunction::LeakyReLU(0.1), 2.0, range(-0.2, 2.0)),
+        (ActivationFunction::RELU6, 8.0, range(0.0, 6.0)),
+    ];
+    for config in configs() {
+        for (activation, weight, output) in cases {
+            assert_within_one_step(&quantized(activation, &[weight], output, &config));
+        }
+    }
+}
+
+/// Every probability lands within one of the `1 / scale` output levels of the float softmax
+/// of the logits on the shared grid.
+#[test]
+fn softmax_levels() {
+    for config in configs() {
+        let layer = quantized(
+            ActivationFunction::Softmax,
+            &[1.0, -2.0, 3.0, 0.5],
+            range(0.0, 1.0),
+            &config,
+        );
+        assert_within_one_step(&layer);
+    }
+}
@@ -0,0 +1,91 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{Array1, Array2};
+use ray_ml::*;
+
+/// A normalization with random parameters and inference statistics.
+fn batch_norm(
+    channels: usize,
+    length: usize,
+    activation: ActivationFunction,
+    rng: &mut StdRng,
+) -> BatchNorm {
+    let mut random =
+        |low: f32, high: f32| Array1::from_shape_fn(channels, |_| rng.gen_range(low..high));
+    BatchNorm {
+        gamma: random(0.5, 2.0),
+        beta: random(-1.0, 1.0),
+        mean: random(-0.5, 0.5),
+        variance: random(0.1, 3.0),
+        ..BatchNorm::new(channels, length, activation)
+    }
+}
+
+/// Folds the network and checks that it computes the same outputs with fewer layers.
+fn assert_fold_keeps_outputs(mut network: NeuralNetwork, input_size: usize, rng: &mut StdRng) {
+    let inputs = Array2::from_shape_fn((16, input_size), |_| rng.gen_range(-2.0..2.0));
+    let expected = network.feedforward_batch(&inputs);
+    let layers = network.layers.len();
+
+    network.fold_batch_norm().unwrap();
+    assert_eq!(network.layers.len(), layers - 1);
+    assert!(!network
+        .layers
+        .iter()
+        .any(|layer| matches!(layer, NetworkLayer::BatchNorm(_))));
+
+    let actual = network.feedforward_batch(&inputs);
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+}
+
+#[test]
+fn fold_into_dense() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let network = NeuralNetwork {
+        layers: vec![
+            Dense::new(5, 6, ActivationFunction::Linear, &mut rng).into(),
+            batch_norm(6, 1, ActivationFunction::ReLU, &mut rng).into(),
+            Dense::new(6, 3, ActivationFunction::Softmax, &mut rng).into(),
+        ],
+    };
+    assert_fold_keeps_outputs(network, 5, &mut rng);
+}
+
+#[test]
+fn fold_into_conv() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let shape = Conv1dShape {
+        stride: 2,
+        padding: 1,
+        ..Conv1dShape::new(2, 9, 3)
+    };
+    let conv = Conv1d::new(shape, 4, ActivationFunction::Linear, &mut rng);
+    let length = shape.output_length();
+    let network = NeuralNetwork {
+        layers: vec![
+            conv.into(),
+            batch_norm(4, length, ActivationFunction::Tanh, &mut rng).into(),
+            Dense::new(4 * length, 2, ActivationFunction::Sigmoid, &mut rng).into(),
+        ],
+    };
+    assert_fold_keeps_outputs(network, 18, &mut rng);
+}
+
+/// The activation of the dense layer would run before the normalization.
+#[test]
+fn fold_needs_linear_layer() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut network = NeuralNetwork {
+        layers: vec![
+            Dense::new(5, 6, ActivationFunction::ReLU, &mut rng).into(),
+            batch_norm(6, 1, ActivationFunction::Linear, &mut rng).into(),
+        ],
+    };
+    let error = network.fold_batch_norm().unwrap_err();
+    assert!(
+        error.to_string().contains("Layer 1 cannot be folded"),
+        "{error}"
+    );
+}
@@ -0,0 +1,56 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+
+/// Range chosen for the network input when it takes the given values.
+fn input_range(values: &[f32], method: CalibrationMethod) -> ActivationRange {
+    let mut rng = StdRng::seed_from_u64(1);
+    let network = NeuralNetwork::new(&[1, 1], &[ActivationFunction::Linear], &mut rng);
+    let data: Vec<DataPoint> = values
+        .iter()
+        .map(|&x| DataPoint {
+            inputs: Array1::from_elem(1, x),
+            targets: Array1::zeros(1),
+        })
+        .collect();
+    network.calibrate(&data, method).ranges[0]
+}
+
+/// 10001 evenly spaced values over [-1, 1].
+fn uniform() -> Vec<f32> {
+    (0..=10000).map(|i| i as f32 / 5000.0 - 1.0).collect()
+}
+
+#[test]
+fn min_max() {
+    let range = input_range(&uniform(), CalibrationMethod::MinMax);
+    assert_eq!((range.min, range.max), (-1.0, 1.0));
+}
+
+#[test]
+fn percentile_clips_both_tails() {
+    // 98% keeps [-0.98, 0.98], up to one histogram bin (2 / 2048).
+    let range = input_range(&uniform(), CalibrationMethod::Percentile(98.0));
+    assert!((range.min + 0.98).abs() < 2e-3, "{range:?}");
+    assert!((range.max - 0.98).abs() < 2e-3, "{range:?}");
+
+    let range = input_range(&uniform(), CalibrationMethod::Percentile(100.0));
+    assert_eq!((range.min, range.max), (-1.0, 1.0));
+}
+
+#[test]
+fn entropy_ignores_outliers() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    let mut values: Vec<f32> = (0..20000).map(|_| normal.sample(&mut rng)).collect();
+    values.push(100.0);
+
+    // The outlier stretches the histogram, but the threshold stays near the bulk.
+    let min_max = input_range(&values, CalibrationMethod::MinMax);
+    assert_eq!(min_max.max, 100.0);
+    let range = input_range(&values, CalibrationMethod::Entropy);
+    assert!(range.max > 2.0 && range.max < 20.0, "{range:?}");
+    assert_eq!(range.min, min_max.min);
+}
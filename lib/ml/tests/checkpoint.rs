@@ -0,0 +1,117 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+
+const EPOCHS: usize = 200;
+
+fn random_data(rng: &mut StdRng) -> Vec<DataPoint> {
+    (0..64)
+        .map(|_| {
+            let inputs = Array1::from_shape_fn(4, |_| rng.gen_range(-1.0..1.0));
+            let class = usize::from(inputs[0] + inputs[1] > 0.0);
+            let mut targets = Array1::zeros(2);
+            targets[class] = 1.0;
+            DataPoint { inputs, targets }
+        })
+        .collect()
+}
+
+fn early_stopping_trainer<'a>(
+    network: &'a mut NeuralNetwork,
+    validation: &'a [DataPoint],
+) -> Trainer<'a> {
+    let mut trainer = Trainer::with_loss(network, 0.1, 0.9, CategoricalCrossEntropy::default());
+    trainer.batch_size = Some(16);
+    trainer.seed = 7;
+    trainer.validation = Some(validation);
+    trainer.early_stopping = Some(EarlyStopping {
+        min_delta: 1e-3,
+        ..EarlyStopping::new(Metric::Loss, 3)
+    });
+    trainer.callbacks.clear();
+    trainer
+}
+
+/// A run interrupted one epoch before early stopping ends it and resumed from its checkpoint
+/// stops at the same epoch with the same best weights as one that was never interrupted.
+#[test]
+fn resume_keeps_early_stopping_progress() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let data = random_data(&mut rng);
+    let validation = random_data(&mut rng);
+    let initial = NeuralNetwork::new(
+        &[4, 8, 2],
+        &[ActivationFunction::Tanh, ActivationFunction::Softmax],
+        &mut rng,
+    );
+
+    let mut uninterrupted = initial.clone();
+    let mut trainer = early_stopping_trainer(&mut uninterrupted, &validation);
+    trainer.train(&data, EPOCHS).unwrap();
+    let stopped_at = trainer.epoch;
+    assert!(stopped_at > 4 && stopped_at < EPOCHS, "{stopped_at}");
+
+    let path = std::env::temp_dir().join("ray-ml-checkpoint-resume.bin");
+    let mut first = initial.clone();
+    let mut trainer = early_stopping_trainer(&mut first, &validation);
+    trainer.checkpoint_path = Some(path.clone());
+    trainer.train(&data, stopped_at - 1).unwrap();
+
+    let mut resumed = initial;
+    let mut trainer = early_stopping_trainer(&mut resumed, &validation);
+    trainer.resume_from_file(&path).unwrap();
+    trainer.train(&data, EPOCHS).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(trainer.epoch, stopped_at);
+    assert!(trainer.early_stopping_state.is_none());
+    assert_eq!(
+        resumed.parameters(),
+        uninterrupted.parameters(),
+        "resumed run restored other weights"
+    );
+}
+
+#[test]
+fn other_optimizer_is_rejected() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let data = random_data(&mut rng);
+    let mut network = NeuralNetwork::new(&[4, 2], &[ActivationFunction::Softmax], &mut rng);
+
+    let mut trainer = Trainer::with_optimizer(
+        &mut network,
+        0.01,
+        Adam::new(),
+        CategoricalCrossEntropy::default(),
+    );
+    trainer.callbacks.clear();
+    trainer.train(&data, 2).unwrap();
+    let checkpoint = trainer.checkpoint();
+
+    let mut trainer =
+        Trainer::with_loss(&mut network, 0.1, 0.9, CategoricalCrossEntropy::default());
+    let error = trainer.resume(checkpoint).unwrap_err();
+    assert!(error.to_string().contains("written by Adam"), "{error}");
+}
+
+#[test]
+fn other_files_are_rejected() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut network = NeuralNetwork::new(&[4, 2], &[ActivationFunction::Softmax], &mut rng);
+    let path = std::env::temp_dir().join("ray-ml-checkpoint-magic.bin");
+
+    Trainer::new(&mut network, 0.1, 0.9)
+        .save_checkpoint(&path)
+        .unwrap();
+    assert!(Checkpoint::load_from_file(&path).is_ok());
+    assert_eq!(&std::fs::read(&path).unwrap()[..5], b"RAYCK");
+
+    network.save_to_file(&path).unwrap();
+    let error = Checkpoint::load_from_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(
+        error.to_string().contains("not a checkpoint file"),
+        "{error}"
+    );
+}
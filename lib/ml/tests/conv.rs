@@ -0,0 +1,99 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{arr1, arr2, Array2};
+use ray_ml::*;
+
+fn shape(
+    input_length: usize,
+    kernel_size: usize,
+    stride: usize,
+    padding: usize,
+    dilation: usize,
+) -> Conv1dShape {
+    Conv1dShape {
+        stride,
+        padding,
+        dilation,
+        ..Conv1dShape::new(2, input_length, kernel_size)
+    }
+}
+
+/// A linear convolution with the given kernel, one input and one output channel.
+fn single_channel(shape: Conv1dShape, kernel: &[f32]) -> Conv1d {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut conv = Conv1d::new(shape, 1, ActivationFunction::Linear, &mut rng);
+    conv.weights = Array2::from_shape_vec((1, kernel.len()), kernel.to_vec()).unwrap();
+    conv
+}
+
+#[test]
+fn output_length() {
+    assert_eq!(shape(10, 3, 1, 0, 1).output_length(), 8);
+    assert_eq!(shape(10, 3, 1, 1, 1).output_length(), 10);
+    assert_eq!(shape(10, 3, 2, 1, 1).output_length(), 5);
+    assert_eq!(shape(10, 3, 1, 0, 2).output_length(), 6);
+    assert_eq!(shape(10, 3, 3, 2, 2).output_length(), 4);
+    assert_eq!(shape(10, 1, 4, 0, 1).output_length(), 3);
+}
+
+/// `[1, 2, 3, 4, 5]` padded to `[0, 1, 2, 3, 4, 5, 0]` and read at every second position.
+#[test]
+fn padding_and_stride() {
+    let shape = Conv1dShape {
+        stride: 2,
+        padding: 1,
+        ..Conv1dShape::new(1, 5, 2)
+    };
+    let conv = single_channel(shape, &[1.0, 10.0]);
+    let output = conv.forward(arr2(&[[1.0, 2.0, 3.0, 4.0, 5.0]]).view());
+    assert_eq!(output, arr2(&[[10.0, 32.0, 54.0]]));
+}
+
+/// Taps three positions apart read `x[0], x[3]` and `x[1], x[4]`.
+#[test]
+fn dilation() {
+    let shape = Conv1dShape {
+        dilation: 3,
+        ..Conv1dShape::new(1, 5, 2)
+    };
+    let conv = single_channel(shape, &[1.0, 10.0]);
+    let output = conv.forward(arr2(&[[1.0, 2.0, 3.0, 4.0, 5.0]]).view());
+    assert_eq!(output, arr2(&[[41.0, 52.0]]));
+}
+
+/// Two channels in, three out, with padding, stride and dilation at once, against the
+/// definition of the convolution.
+#[test]
+fn matches_direct_convolution() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let shape = shape(10, 3, 3, 2, 2);
+    let mut conv = Conv1d::new(shape, 3, ActivationFunction::Linear, &mut rng);
+    conv.biases = arr1(&[0.5, -0.25, 1.0]);
+    let input = Array2::from_shape_fn((2, 20), |_| rng.gen_range(-1.0..1.0));
+
+    let length = shape.output_length();
+    let output = conv.forward(input.view());
+    assert_eq!(output.dim(), (2, 3 * length));
+    assert_eq!(conv.output_size(), 3 * length);
+
+    for sample in 0..2 {
+        for out_channel in 0..3 {
+            for position in 0..length {
+                let mut expected = conv.biases[out_channel];
+                for channel in 0..2 {
+                    for tap in 0..3 {
+                        let Some(source) = (position * 3 + tap * 2).checked_sub(2) else {
+                            continue;
+                        };
+                        if source < 10 {
+                            expected += conv.weights[[out_channel, channel * 3 + tap]]
+                                * input[[sample, channel * 10 + source]];
+                        }
+                    }
+                }
+                let actual = output[[sample, out_channel * length + position]];
+                assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{s, Array1};
+use ray_ml::*;
+
+/// Two channels of 16 values with a one-hot label of which channel has the larger mean.
+fn random_data(rng: &mut StdRng) -> Vec<DataPoint> {
+    (0..300)
+        .map(|_| {
+            let inputs = Array1::from_shape_fn(32, |_| rng.gen_range(-1.0..1.0));
+            let class = usize::from(inputs.slice(s![16..]).sum() > inputs.slice(s![..16]).sum());
+            let mut targets = Array1::zeros(2);
+            targets[class] = 1.0;
+            DataPoint { inputs, targets }
+        })
+        .collect()
+}
+
+fn network(rng: &mut StdRng) -> NeuralNetwork {
+    let conv = Conv1d::new(Conv1dShape::new(2, 16, 3), 4, ActivationFunction::ReLU, rng);
+    let gru = Gru::new(4, 6, 14, rng);
+    let dense = Dense::new(6, 2, ActivationFunction::Softmax, rng);
+    NeuralNetwork {
+        layers: vec![conv.into(), gru.into(), dense.into()],
+    }
+}
+
+/// Trains on a rayon pool of `threads` threads and returns the bits of every parameter.
+fn train(threads: usize) -> Vec<u32> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = random_data(&mut rng);
+        let mut network = network(&mut rng);
+
+        let mut trainer = Trainer::with_optimizer(
+            &mut network,
+            0.01,
+            Adam::new(),
+            CategoricalCrossEntropy::default(),
+        );
+        trainer.batch_size = Some(100);
+        trainer.seed = 2;
+        trainer.callbacks.clear();
+        trainer.train(&data, 3).unwrap();
+
+        network
+            .parameters()
+            .iter()
+            .flatten()
+            .map(|w| w.to_bits())
+            .collect()
+    })
+}
+
+/// The gradients are summed in a fixed tree and the matrix products in a fixed order, so
+/// the thread count cannot change a single bit of the trained weights.
+#[test]
+fn thread_count_does_not_change_weights() {
+    let single = train(1);
+    for threads in [2, 3, 8] {
+        assert!(
+            train(threads) == single,
+            "{threads} threads trained other weights"
+        );
+    }
+}
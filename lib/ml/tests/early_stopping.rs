@@ -0,0 +1,107 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+use ray_shared::result::Result;
+use std::sync::{Arc, Mutex};
+
+const PATIENCE: usize = 3;
+
+/// Keeps the summary of the last `train` call.
+struct SummaryRecorder(Arc<Mutex<Option<TrainingSummary>>>);
+
+impl Callback for SummaryRecorder {
+    fn on_epoch_end(&mut self, _metrics: &EpochMetrics) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_train_end(&mut self, summary: &TrainingSummary) -> Result<()> {
+        *self.0.lock().unwrap() = Some(summary.clone());
+        Ok(())
+    }
+}
+
+/// Training data and validation data with the opposite labels, so every epoch that fits the
+/// training data better makes the validation loss worse and the first evaluation stays best.
+fn diverging_data() -> (Vec<DataPoint>, Vec<DataPoint>) {
+    let mut rng = StdRng::seed_from_u64(1);
+    let data: Vec<DataPoint> = (0..40)
+        .map(|_| {
+            let inputs = Array1::from_shape_fn(3, |_| rng.gen_range(-1.0..1.0));
+            let targets = Array1::from_elem(1, f32::from(inputs[0] > 0.0));
+            DataPoint { inputs, targets }
+        })
+        .collect();
+    let validation = data
+        .iter()
+        .map(|point| DataPoint {
+            inputs: point.inputs.clone(),
+            targets: 1.0 - &point.targets,
+        })
+        .collect();
+    (data, validation)
+}
+
+fn network() -> NeuralNetwork {
+    let mut rng = StdRng::seed_from_u64(2);
+    NeuralNetwork::new(
+        &[3, 4, 1],
+        &[ActivationFunction::Tanh, ActivationFunction::Sigmoid],
+        &mut rng,
+    )
+}
+
+/// Trains for `epochs` without early stopping.
+fn trained(data: &[DataPoint], epochs: usize) -> NeuralNetwork {
+    let mut network = network();
+    let mut trainer = Trainer::new(&mut network, 0.5, 0.9);
+    trainer.callbacks.clear();
+    trainer.train(data, epochs).unwrap();
+    network
+}
+
+/// Runs with early stopping and returns the network, the epochs run and the summary.
+fn stopped(restore_best: bool) -> (NeuralNetwork, usize, TrainingSummary) {
+    let (data, validation) = diverging_data();
+    let summary = Arc::new(Mutex::new(None));
+    let mut network = network();
+    let mut trainer = Trainer::new(&mut network, 0.5, 0.9);
+    trainer.validation = Some(&validation);
+    trainer.early_stopping = Some(EarlyStopping {
+        restore_best,
+        ..EarlyStopping::new(Metric::Loss, PATIENCE)
+    });
+    trainer.callbacks = vec![Box::new(SummaryRecorder(summary.clone()))];
+    trainer.train(&data, 100).unwrap();
+
+    let epochs = trainer.epoch;
+    let summary = summary.lock().unwrap().take().unwrap();
+    (network, epochs, summary)
+}
+
+#[test]
+fn stops_after_patience() {
+    let (_, epochs, summary) = stopped(true);
+    assert_eq!(summary.best_epoch, Some(0));
+    assert!(summary.stopped_early);
+    assert_eq!(epochs, PATIENCE + 1);
+    assert_eq!(summary.epochs, PATIENCE + 1);
+}
+
+#[test]
+fn restores_best_weights() {
+    let (data, _) = diverging_data();
+    let (network, _, _) = stopped(true);
+    assert_eq!(network.parameters(), trained(&data, 1).parameters());
+
+    let (network, epochs, _) = stopped(false);
+    assert_eq!(network.parameters(), trained(&data, epochs).parameters());
+}
+
+#[test]
+fn metric_direction() {
+    assert!(Metric::Loss.improves(0.5, 0.6, 0.05));
+    assert!(!Metric::Loss.improves(0.58, 0.6, 0.05));
+    assert!(Metric::Accuracy.improves(0.9, 0.8, 0.05));
+    assert!(!Metric::Accuracy.improves(0.7, 0.8, 0.0));
+}
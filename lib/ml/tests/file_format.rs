@@ -0,0 +1,105 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{Array1, Array2};
+use ray_ml::*;
+use serde::Serialize;
+
+/// The quantized layer the first release wrote, without a file magic.
+#[derive(Serialize)]
+struct BaselineQuantizedLayer {
+    weights: Array2<i8>,
+    biases: Array1<i32>,
+    activation: ActivationFunction,
+    weight_scale: f32,
+    bias_scale: f32,
+}
+
+#[derive(Serialize)]
+struct BaselineQuantizedNetwork {
+    layers: Vec<BaselineQuantizedLayer>,
+}
+
+const BASELINE_SIGMOID_TABLE: [i8; 17] = [
+    0, 4, 8, 15, 26, 41, 60, 81, 103, 122, 127, 127, 127, 127, 127, 127, 127,
+];
+
+/// `QuantizedNeuralNetwork::feedforward` of the first release.
+fn baseline_feedforward(network: &BaselineQuantizedNetwork, input: &Array1<i8>) -> Array1<i8> {
+    network
+        .layers
+        .iter()
+        .fold(input.clone(), |activations, layer| {
+            let z = layer
+                .weights
+                .mapv(|w| w as i32)
+                .dot(&activations.mapv(|a| a as i32))
+                + &layer.biases;
+            match layer.activation {
+                ActivationFunction::Sigmoid => {
+                    z.mapv(|x| BASELINE_SIGMOID_TABLE[((x >> 7).clamp(-8, 8) + 8) as usize])
+                }
+                ActivationFunction::ReLU => z.mapv(|x| x.clamp(0, 127) as i8),
+                _ => z.mapv(|x| x.clamp(-128, 127) as i8),
+            }
+        })
+}
+
+fn random_layer(
+    rng: &mut StdRng,
+    inputs: usize,
+    outputs: usize,
+    activation: ActivationFunction,
+) -> BaselineQuantizedLayer {
+    BaselineQuantizedLayer {
+        weights: Array2::from_shape_fn((outputs, inputs), |_| rng.gen()),
+        biases: Array1::from_shape_fn(outputs, |_| rng.gen_range(-2000..2000)),
+        activation,
+        weight_scale: 0.01,
+        bias_scale: 0.01,
+    }
+}
+
+#[test]
+fn baseline_quantized_network() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let baseline = BaselineQuantizedNetwork {
+        layers: vec![
+            random_layer(&mut rng, 6, 8, ActivationFunction::ReLU),
+            random_layer(&mut rng, 8, 8, ActivationFunction::Linear),
+            random_layer(&mut rng, 8, 4, ActivationFunction::Sigmoid),
+        ],
+    };
+    let path = std::env::temp_dir().join("ray-ml-baseline-quantized.bin");
+    std::fs::write(&path, bincode::serialize(&baseline).unwrap()).unwrap();
+    let network = QuantizedNeuralNetwork::load_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    for _ in 0..64 {
+        let input = Array1::from_shape_fn(6, |_| rng.gen());
+        assert_eq!(
+            network.feedforward(&input),
+            baseline_feedforward(&baseline, &input)
+        );
+    }
+}
+
+#[test]
+fn other_quantized_version() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let network = NeuralNetwork::new(&[4, 3], &[ActivationFunction::Sigmoid], &mut rng);
+    let path = std::env::temp_dir().join("ray-ml-other-quantized-version.bin");
+    network.quantize().unwrap().save_to_file(&path).unwrap();
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[7] -= 1;
+    std::fs::write(&path, bytes).unwrap();
+    let error = QuantizedNeuralNetwork::load_from_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unsupported quantized network version"),
+        "{error}"
+    );
+}
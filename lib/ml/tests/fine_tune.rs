@@ -0,0 +1,88 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+use ray_shared::result::Result;
+use std::sync::{Arc, Mutex};
+
+/// Keeps the training loss of every epoch.
+struct LossRecorder(Arc<Mutex<Vec<f32>>>);
+
+impl Callback for LossRecorder {
+    fn on_epoch_end(&mut self, metrics: &EpochMetrics) -> Result<()> {
+        self.0.lock().unwrap().push(metrics.loss);
+        Ok(())
+    }
+}
+
+fn random_data(rng: &mut StdRng) -> Vec<DataPoint> {
+    (0..128)
+        .map(|_| {
+            let inputs = Array1::from_shape_fn(4, |_| rng.gen_range(-1.0..1.0));
+            let class = usize::from(inputs[0] * inputs[1] > 0.0);
+            let mut targets = Array1::zeros(2);
+            targets[class] = 1.0;
+            DataPoint { inputs, targets }
+        })
+        .collect()
+}
+
+fn classifier(rng: &mut StdRng) -> NeuralNetwork {
+    NeuralNetwork::new(
+        &[4, 12, 2],
+        &[ActivationFunction::Tanh, ActivationFunction::Softmax],
+        rng,
+    )
+}
+
+/// The loss `fine_tune` trains on is the one of the integer network, not the float one.
+#[test]
+fn forward_pass_is_the_integer_network() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let data = random_data(&mut rng);
+    let mut network = classifier(&mut rng);
+    let loss = CategoricalCrossEntropy::default();
+    let expected = network.quantize().unwrap().evaluate(&data, &loss).loss;
+    assert_ne!(expected, network.evaluate(&data, &loss).loss);
+
+    let losses = Arc::new(Mutex::new(Vec::new()));
+    let mut trainer = Trainer::with_loss(&mut network, 0.1, 0.9, loss);
+    trainer.callbacks = vec![Box::new(LossRecorder(losses.clone()))];
+    trainer.fine_tune(&data, 1).unwrap();
+
+    let actual = losses.lock().unwrap()[0];
+    assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+}
+
+/// Gradients through the straight-through estimator lower the loss of the integer network.
+#[test]
+fn fine_tune_lowers_quantized_loss() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let data = random_data(&mut rng);
+    let mut network = classifier(&mut rng);
+    let loss = CategoricalCrossEntropy::default();
+
+    let before = network.quantize().unwrap().evaluate(&data, &loss).loss;
+    let mut trainer = Trainer::with_loss(&mut network, 0.1, 0.9, loss.clone());
+    trainer.batch_size = Some(16);
+    trainer.callbacks.clear();
+    trainer.fine_tune(&data, 30).unwrap();
+    let after = network.quantize().unwrap().evaluate(&data, &loss).loss;
+
+    assert!(after < 0.8 * before, "{after} >= {before}");
+}
+
+#[test]
+fn fine_tune_rejects_batch_norm() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let data = random_data(&mut rng);
+    let mut network = classifier(&mut rng);
+    network.layers.insert(
+        1,
+        NetworkLayer::BatchNorm(BatchNorm::new(12, 1, ActivationFunction::Linear)),
+    );
+
+    let mut trainer = Trainer::new(&mut network, 0.1, 0.9);
+    let error = trainer.fine_tune(&data, 1).unwrap_err();
+    assert!(error.to_string().contains("fold_batch_norm"), "{error}");
+}
@@ -0,0 +1,269 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+
+/// Small enough that the steps rarely cross a kink of the piecewise-linear activations.
+const EPSILON: f32 = 1e-3;
+const TOLERANCE: f32 = 1e-2;
+
+/// Random inputs in `[-1, 1]` with one-hot or `[0, 1]` targets.
+fn random_data(
+    rng: &mut StdRng,
+    count: usize,
+    inputs: usize,
+    outputs: usize,
+    one_hot: bool,
+) -> Vec<DataPoint> {
+    (0..count)
+        .map(|_| {
+            let mut targets = Array1::zeros(outputs);
+            if one_hot {
+                targets[rng.gen_range(0..outputs)] = 1.0;
+            } else {
+                targets.mapv_inplace(|_: f32| rng.gen());
+            }
+            DataPoint {
+                inputs: Array1::from_shape_fn(inputs, |_| rng.gen_range(-1.0..1.0)),
+                targets,
+            }
+        })
+        .collect()
+}
+
+fn assert_gradients(mut network: NeuralNetwork, loss: impl Loss + 'static, data: &[DataPoint]) {
+    let trainer = Trainer::with_loss(&mut network, 0.1, 0.0, loss);
+    let check = trainer.check_gradients(data, EPSILON).unwrap();
+    assert_eq!(check.layers.len(), trainer.network.layers.len());
+    assert!(check.max_error() < TOLERANCE, "{:?}", check);
+}
+
+const ELEMENT_WISE: [ActivationFunction; 10] = [
+    ActivationFunction::ReLU,
+    ActivationFunction::Sigmoid,
+    ActivationFunction::Linear,
+    ActivationFunction::Tanh,
+    ActivationFunction::LeakyReLU(0.1),
+    ActivationFunction::RELU6,
+    ActivationFunction::HardSigmoid,
+    ActivationFunction::HardSwish,
+    ActivationFunction::SiLU,
+    ActivationFunction::GELU,
+];
+
+#[test]
+fn hidden_activations() {
+    for activation in ELEMENT_WISE {
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = random_data(&mut rng, 4, 4, 3, false);
+        let network = NeuralNetwork::new(
+            &[4, 6, 3],
+            &[activation, ActivationFunction::Linear],
+            &mut rng,
+        );
+        assert_gradients(network, MeanSquaredError, &data);
+    }
+}
+
+#[test]
+fn output_activations() {
+    for activation in ELEMENT_WISE {
+        let mut rng = StdRng::seed_from_u64(2);
+        let data = random_data(&mut rng, 4, 4, 3, false);
+        let network = NeuralNetwork::new(
+            &[4, 6, 3],
+            &[ActivationFunction::Tanh, activation],
+            &mut rng,
+        );
+        assert_gradients(network, MeanSquaredError, &data);
+    }
+}
+
+#[test]
+fn losses() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let one_hot = random_data(&mut rng, 4, 4, 3, true);
+    let soft = random_data(&mut rng, 4, 4, 3, false);
+    let softmax = NeuralNetwork::new(
+        &[4, 5, 3],
+        &[ActivationFunction::Sigmoid, ActivationFunction::Softmax],
+        &mut rng,
+    );
+    let sigmoid = NeuralNetwork::new(
+        &[4, 5, 3],
+        &[ActivationFunction::Tanh, ActivationFunction::Sigmoid],
+        &mut rng,
+    );
+    let linear = NeuralNetwork::new(
+        &[4, 5, 3],
+        &[ActivationFunction::Tanh, ActivationFunction::Linear],
+        &mut rng,
+    );
+
+    assert_gradients(
+        softmax.clone(),
+        CategoricalCrossEntropy::default(),
+        &one_hot,
+    );
+    assert_gradients(softmax.clone(), FocalLoss::default(), &one_hot);
+    assert_gradients(
+        softmax,
+        FocalLoss {
+            gamma: 0.5,
+            class_weights: None,
+        },
+        &one_hot,
+    );
+    assert_gradients(sigmoid, BinaryCrossEntropy::default(), &soft);
+    assert_gradients(linear.clone(), Huber::default(), &soft);
+    assert_gradients(linear, HalfSquaredError, &soft);
+}
+
+/// With `gamma < 1` the modulation's derivative diverges at `p = 1`, which must not turn a
+/// correct, saturated output into a NaN gradient.
+#[test]
+fn saturated_focal_loss() {
+    let loss = FocalLoss {
+        gamma: 0.5,
+        class_weights: None,
+    };
+    let output = Array1::from(vec![0.0, 1.0, 0.0]);
+    let target = Array1::from(vec![0.0, 1.0, 0.0]);
+
+    let gradient = loss.gradient(&output, &target);
+    assert!(gradient.iter().all(|g| g.abs() < 1e-3), "{gradient}");
+    assert!(loss.value(&output, &target).abs() < 1e-6);
+}
+
+#[test]
+fn regularization() {
+    let mut rng = StdRng::seed_from_u64(10);
+    let data = random_data(&mut rng, 4, 4, 3, false);
+    let mut network = NeuralNetwork::new(
+        &[4, 6, 3],
+        &[ActivationFunction::Tanh, ActivationFunction::Sigmoid],
+        &mut rng,
+    );
+    let mut trainer = Trainer::new(&mut network, 0.1, 0.0);
+    trainer.regularization = vec![Regularization::l1(0.1), Regularization::l2(0.5)];
+    let check = trainer.check_gradients(&data, EPSILON).unwrap();
+    assert!(check.max_error() < TOLERANCE, "{:?}", check);
+}
+
+/// Two channels of eight values through `hidden`, then a dense softmax output.
+fn sequence_network(rng: &mut StdRng, hidden: Vec<NetworkLayer>) -> NeuralNetwork {
+    let size = hidden.last().unwrap().output_size();
+    let mut layers = hidden;
+    layers.push(Dense::new(size, 3, ActivationFunction::Softmax, rng).into());
+    NeuralNetwork { layers }
+}
+
+fn assert_sequence_gradients(seed: u64, hidden: impl FnOnce(&mut StdRng) -> Vec<NetworkLayer>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let data = random_data(&mut rng, 4, 16, 3, true);
+    let layers = hidden(&mut rng);
+    let network = sequence_network(&mut rng, layers);
+    assert_gradients(network, CategoricalCrossEntropy::default(), &data);
+}
+
+#[test]
+fn conv1d() {
+    assert_sequence_gradients(4, |rng| {
+        let shape = Conv1dShape {
+            stride: 2,
+            padding: 1,
+            dilation: 2,
+            ..Conv1dShape::new(2, 8, 3)
+        };
+        vec![Conv1d::new(shape, 3, ActivationFunction::Tanh, rng).into()]
+    });
+}
+
+#[test]
+fn pool1d() {
+    for pooling in [Pooling::Max, Pooling::Average] {
+        assert_sequence_gradients(5, |rng| {
+            let conv = Conv1d::new(
+                Conv1dShape::new(2, 8, 3),
+                3,
+                ActivationFunction::Sigmoid,
+                rng,
+            );
+            vec![conv.into(), Pool1d::new(pooling, 3, 6, 2).into()]
+        });
+    }
+    assert_sequence_gradients(5, |_| vec![Pool1d::global_average(2, 8).into()]);
+}
+
+#[test]
+fn reshape() {
+    assert_sequence_gradients(6, |rng| {
+        vec![
+            Reshape::new(vec![16], vec![2, 8]).into(),
+            Conv1d::new(Conv1dShape::new(2, 8, 2), 2, ActivationFunction::Tanh, rng).into(),
+            Reshape::flatten(vec![2, 7]).into(),
+        ]
+    });
+}
+
+#[test]
+fn dropout() {
+    assert_sequence_gradients(7, |rng| {
+        vec![
+            Dense::new(16, 8, ActivationFunction::Sigmoid, rng).into(),
+            Dropout::new(8, 0.5).into(),
+        ]
+    });
+}
+
+#[test]
+fn batch_norm() {
+    // Normalization cancels the bias of a linear layer before it, whose gradient is then
+    // zero up to rounding, so the layers before it are non-linear.
+    assert_sequence_gradients(8, |rng| {
+        vec![
+            Dense::new(16, 6, ActivationFunction::Tanh, rng).into(),
+            BatchNorm::new(6, 1, ActivationFunction::Tanh).into(),
+        ]
+    });
+    assert_sequence_gradients(8, |rng| {
+        let conv = Conv1d::new(Conv1dShape::new(2, 8, 3), 3, ActivationFunction::Tanh, rng);
+        vec![
+            conv.into(),
+            BatchNorm::new(3, 6, ActivationFunction::Sigmoid).into(),
+        ]
+    });
+
+    // A single sample is normalized with the inference statistics.
+    let mut rng = StdRng::seed_from_u64(8);
+    let data = random_data(&mut rng, 1, 16, 3, true);
+    let mut norm = BatchNorm::new(6, 1, ActivationFunction::Tanh);
+    norm.mean.fill(0.5);
+    norm.variance.fill(2.0);
+    let hidden = vec![
+        Dense::new(16, 6, ActivationFunction::Linear, &mut rng).into(),
+        norm.into(),
+    ];
+    let network = sequence_network(&mut rng, hidden);
+    assert_gradients(network, CategoricalCrossEntropy::default(), &data);
+}
+
+#[test]
+fn gru() {
+    for return_sequences in [false, true] {
+        assert_sequence_gradients(9, |rng| {
+            let mut gru = Gru::new(2, 4, 8, rng);
+            gru.return_sequences = return_sequences;
+            vec![gru.into()]
+        });
+    }
+}
+
+#[test]
+fn empty_data() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let mut network = NeuralNetwork::new(&[4, 3], &[ActivationFunction::Sigmoid], &mut rng);
+    let trainer = Trainer::new(&mut network, 0.1, 0.0);
+    let error = trainer.check_gradients(&[], EPSILON).unwrap_err();
+    assert!(error.to_string().contains("without data"), "{error}");
+}
@@ -0,0 +1,149 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{s, Array1, Axis};
+use ray_ml::*;
+
+const INPUT_SIZE: usize = 3;
+const LENGTH: usize = 6;
+
+/// Channels of `LENGTH` values in `[-1, 1]`.
+fn random_sequence(rng: &mut StdRng) -> Array1<f32> {
+    Array1::from_shape_fn(INPUT_SIZE * LENGTH, |_| rng.gen_range(-1.0..1.0))
+}
+
+/// Two stacked GRU layers, the first returning sequences, and a dense head.
+fn stacked(rng: &mut StdRng) -> NeuralNetwork {
+    let mut first = Gru::new(INPUT_SIZE, 5, LENGTH, rng);
+    first.return_sequences = true;
+    NeuralNetwork {
+        layers: vec![
+            first.into(),
+            Gru::new(5, 4, LENGTH, rng).into(),
+            Dense::new(4, 2, ActivationFunction::Tanh, rng).into(),
+        ],
+    }
+}
+
+fn frame<T: Copy>(sequence: &Array1<T>, t: usize) -> Array1<T> {
+    sequence.slice(s![t..;LENGTH]).to_owned()
+}
+
+fn assert_close(actual: &Array1<f32>, expected: &Array1<f32>, tolerance: f32) {
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} != {expected}"
+        );
+    }
+}
+
+/// Every hidden state `return_sequences` outputs is the state after that step, and the last
+/// one is the output without it.
+#[test]
+fn return_sequences() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut gru = Gru::new(INPUT_SIZE, 4, LENGTH, &mut rng);
+    let input = random_sequence(&mut rng);
+    let last = gru
+        .forward(input.view().insert_axis(Axis(0)))
+        .row(0)
+        .to_owned();
+
+    gru.return_sequences = true;
+    assert_eq!(gru.output_size(), 4 * LENGTH);
+    let sequence = gru
+        .forward(input.view().insert_axis(Axis(0)))
+        .row(0)
+        .to_owned();
+
+    let mut hidden = gru.initial_state();
+    for t in 0..LENGTH {
+        hidden = gru.step(&frame(&input, t), &hidden);
+        assert_close(&frame(&sequence, t), &hidden, 1e-6);
+    }
+    assert_close(&last, &hidden, 1e-6);
+}
+
+/// A stream fed the frames one by one ends at the output of the whole sequence, also after a
+/// reset.
+#[test]
+fn stream_matches_forward() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let network = stacked(&mut rng);
+    let mut stream = GruStream::new(&network).unwrap();
+
+    for _ in 0..3 {
+        let input = random_sequence(&mut rng);
+        let mut output = Array1::zeros(0);
+        for t in 0..LENGTH {
+            output = stream.push(&frame(&input, t));
+        }
+        assert_close(&output, &network.feedforward(&input), 1e-6);
+        stream.reset();
+    }
+}
+
+/// The integer stream runs the same integer steps as the quantized network, so they agree
+/// exactly.
+#[test]
+fn quantized_stream_matches_feedforward() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let network = stacked(&mut rng).quantize().unwrap();
+    let mut stream = QuantizedGruStream::new(&network).unwrap();
+    let input_params = network.input_params();
+
+    for _ in 0..3 {
+        let input = random_sequence(&mut rng).mapv(|x| input_params.quantize(x));
+        let mut output = Array1::zeros(0);
+        for t in 0..LENGTH {
+            output = stream.push(&frame(&input, t));
+        }
+        assert_eq!(output, network.feedforward(&input));
+        stream.reset();
+    }
+}
+
+/// Streams need the GRU layers first, all but the last of them returning sequences.
+#[test]
+fn stream_needs_leading_grus() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut network = stacked(&mut rng);
+    let NetworkLayer::Gru(first) = &mut network.layers[0] else {
+        unreachable!()
+    };
+    first.return_sequences = false;
+    assert!(GruStream::new(&network).is_err());
+}
+
+/// Every quantized hidden state stays within `MAX_ERROR` of the float state, ten steps of its
+/// `1/128` grid, and within `MEAN_ERROR` on average. Weight and input rounding add up over the
+/// steps of a sequence, which is why the bound is wider than a single step.
+#[test]
+fn quantized_gru_follows_float() {
+    const MAX_ERROR: f32 = 10.0 / 128.0;
+    const MEAN_ERROR: f32 = 2.0 / 128.0;
+
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut gru = Gru::new(INPUT_SIZE, 8, LENGTH, &mut rng);
+    gru.return_sequences = true;
+    let network = NeuralNetwork {
+        layers: vec![gru.into()],
+    };
+    let quantized = network.quantize().unwrap();
+    let (input_params, output_params) = (quantized.input_params(), quantized.output_params());
+
+    let mut errors = Vec::new();
+    for _ in 0..100 {
+        let input = random_sequence(&mut rng);
+        let expected = network.feedforward(&input);
+        let actual = quantized
+            .feedforward(&input.mapv(|x| input_params.quantize(x)))
+            .mapv(|q| output_params.dequantize(q));
+        errors.extend((actual - expected).iter().map(|error| error.abs()));
+    }
+
+    let max_error = errors.iter().fold(0.0f32, |a, &b| a.max(b));
+    let mean_error = errors.iter().sum::<f32>() / errors.len() as f32;
+    assert!(max_error < MAX_ERROR, "largest error {max_error}");
+    assert!(mean_error < MEAN_ERROR, "mean error {mean_error}");
+}
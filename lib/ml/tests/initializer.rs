@@ -0,0 +1,62 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use ray_ml::nd::{s, Array2};
+use ray_ml::*;
+
+fn assert_orthogonal(weights: Array2<f32>) {
+    let product = weights.dot(&weights.t());
+    for ((i, j), &value) in product.indexed_iter() {
+        let expected = if i == j { 1.0 } else { 0.0 };
+        assert!((value - expected).abs() < 1e-4, "{product}");
+    }
+}
+
+#[test]
+fn gru_recurrent_gates_are_orthogonal() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let gru = Gru::with_initializer(3, 5, 4, &Initializer::Orthogonal(1.0), &mut rng);
+    for gate in 0..3 {
+        let weights = gru
+            .recurrent_weights
+            .slice(s![gate * 5..(gate + 1) * 5, ..]);
+        assert_orthogonal(weights.to_owned());
+    }
+}
+
+#[test]
+fn conv1d_initializer() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let conv = Conv1d::with_initializer(
+        Conv1dShape::new(2, 8, 3),
+        4,
+        ActivationFunction::ReLU,
+        &Initializer::Constant(0.25),
+        &mut rng,
+    );
+    assert!(conv.weights.iter().all(|&weight| weight == 0.25));
+}
+
+/// Layers built from layer constructors take over the matching layers of a pretrained
+/// network and keep their own weights elsewhere.
+#[test]
+fn transfer_parameters() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let shape = Conv1dShape::new(2, 8, 3);
+    let conv_network = |rng: &mut StdRng, classes: usize| NeuralNetwork {
+        layers: vec![
+            Conv1d::new(shape, 3, ActivationFunction::ReLU, rng).into(),
+            Pool1d::global_average(3, 6).into(),
+            Dense::new(3, classes, ActivationFunction::Softmax, rng).into(),
+        ],
+    };
+    let source = conv_network(&mut rng, 2);
+    let mut network = conv_network(&mut rng, 4);
+    let head = network.layers[2].parameters()[0].to_owned();
+
+    assert_eq!(network.transfer_parameters(&source), 1);
+    assert_eq!(
+        network.layers[0].parameters(),
+        source.layers[0].parameters()
+    );
+    assert_eq!(network.layers[2].parameters()[0], head);
+}
@@ -0,0 +1,96 @@
+use ray_ml::nd::{arr1, arr2, ArrayD};
+use ray_ml::*;
+
+const LEARNING_RATE: f32 = 0.1;
+const TOLERANCE: f32 = 1e-6;
+
+/// Runs one optimizer step per gradient on `param` in slot 0 and returns the values after each.
+fn steps(optimizer: &mut dyn Optimizer, param: &mut ArrayD<f32>, grads: &[f32]) -> Vec<f32> {
+    grads
+        .iter()
+        .map(|&g| {
+            optimizer.begin_step();
+            let grad = ArrayD::from_elem(param.raw_dim(), g);
+            optimizer.update(0, param.view_mut(), grad.view(), LEARNING_RATE);
+            param.iter().next().copied().unwrap()
+        })
+        .collect()
+}
+
+fn scalar() -> ArrayD<f32> {
+    arr1(&[1.0]).into_dyn()
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < TOLERANCE, "{actual:?} != {expected:?}");
+    }
+}
+
+#[test]
+fn sgd_momentum() {
+    // v = 0.9 v - 0.1 g, w += v
+    let values = steps(&mut Sgd::new(0.9), &mut scalar(), &[0.5, 0.5]);
+    assert_close(&values, &[0.95, 0.855]);
+}
+
+#[test]
+fn sgd_nesterov() {
+    // v = 0.9 v - 0.1 g, w += 0.9 v - 0.1 g
+    let values = steps(&mut Sgd::nesterov(0.9), &mut scalar(), &[0.5, 0.5]);
+    assert_close(&values, &[0.905, 0.7695]);
+}
+
+#[test]
+fn adam() {
+    // Bias correction makes the first step exactly the learning rate.
+    let values = steps(&mut Adam::new(), &mut scalar(), &[0.5, -0.25]);
+    assert_close(&values, &[0.9, 0.873_366_3]);
+}
+
+#[test]
+fn adam_w_decays_matrices_only() {
+    // The decay of 0.1 * 0.01 * w comes on top of the plain Adam step; the 1-D bias skips it.
+    let mut weights = arr2(&[[1.0]]).into_dyn();
+    let values = steps(&mut Adam::with_weight_decay(0.01), &mut weights, &[0.5]);
+    assert_close(&values, &[0.899]);
+    let values = steps(&mut Adam::with_weight_decay(0.01), &mut scalar(), &[0.5]);
+    assert_close(&values, &[0.9]);
+}
+
+#[test]
+fn rms_prop() {
+    // s = 0.1 g², w -= 0.1 g / sqrt(s)
+    let values = steps(&mut RmsProp::new(), &mut scalar(), &[0.5]);
+    assert_close(&values, &[1.0 - 0.1 * 0.5 / 0.025f32.sqrt()]);
+}
+
+/// An optimizer restored from a saved state continues exactly like the one that saved it.
+#[test]
+fn state_round_trip() {
+    let optimizers: [fn() -> Box<dyn Optimizer>; 4] = [
+        || Box::new(Sgd::new(0.9)),
+        || Box::new(Sgd::nesterov(0.9)),
+        || Box::new(Adam::with_weight_decay(0.01)),
+        || Box::new(RmsProp::new()),
+    ];
+
+    for optimizer in optimizers {
+        let mut original = optimizer();
+        let mut param = arr2(&[[1.0, -0.5], [0.25, 2.0]]).into_dyn();
+        steps(original.as_mut(), &mut param, &[0.5, -0.3]);
+
+        let state = bincode::serialize(&original.state()).unwrap();
+        let mut restored = optimizer();
+        restored
+            .load_state(bincode::deserialize(&state).unwrap())
+            .unwrap();
+
+        let mut restored_param = param.clone();
+        let expected = steps(original.as_mut(), &mut param, &[0.2, 0.7]);
+        let actual = steps(restored.as_mut(), &mut restored_param, &[0.2, 0.7]);
+        assert_eq!(actual, expected);
+        assert_eq!(restored_param, param);
+    }
+}
@@ -0,0 +1,90 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{arr1, Array1, Axis};
+use ray_ml::*;
+
+/// A power of two, so dequantized values and their sums are exact.
+const SCALE: f32 = 0.5;
+
+fn quantized(pool: Pool1d, zero_point: i32) -> QuantizedPool1d {
+    QuantizedPool1d {
+        pool,
+        params: QuantizationParams {
+            scale: SCALE,
+            zero_point,
+        },
+    }
+}
+
+/// Pools the dequantized input with the float layer and quantizes the result.
+fn reference(layer: &QuantizedPool1d, input: &Array1<i8>) -> Array1<i8> {
+    let input = input.mapv(|q| layer.params.dequantize(q));
+    layer
+        .pool
+        .forward(input.view().insert_axis(Axis(0)))
+        .row(0)
+        .mapv(|x| layer.params.quantize(x))
+}
+
+#[test]
+fn max() {
+    let layer = quantized(Pool1d::new(Pooling::Max, 2, 4, 2), -20);
+    let input = arr1(&[-128, 5, 7, -3, 127, 126, -50, -50]);
+    let output = layer.feedforward(&input);
+    assert_eq!(output, arr1(&[5, 7, 127, -50]));
+    assert_eq!(output, reference(&layer, &input));
+}
+
+/// Averages relative to the zero point -20 are 3.5, -3.5, -0.5 and -107.5, which round half
+/// away from zero.
+#[test]
+fn average_rounds_half_away_from_zero() {
+    let layer = quantized(Pool1d::new(Pooling::Average, 2, 4, 2), -20);
+    let input = arr1(&[-17, -16, -23, -24, -19, -22, -128, -127]);
+    let output = layer.feedforward(&input);
+    assert_eq!(output, arr1(&[-16, -24, -21, -128]));
+    assert_eq!(output, reference(&layer, &input));
+}
+
+/// Thirds round to the nearest integer: 2/3 up to 1, -2/3 down to -1 and 1/3 to 0.
+#[test]
+fn average_of_three() {
+    let layer = quantized(Pool1d::new(Pooling::Average, 1, 9, 3), 10);
+    let input = arr1(&[11, 11, 10, 9, 9, 10, 11, 10, 10]);
+    let output = layer.feedforward(&input);
+    assert_eq!(output, arr1(&[11, 9, 10]));
+    assert_eq!(output, reference(&layer, &input));
+}
+
+/// Random inputs over overlapping and global windows and zero points on both sides of zero.
+#[test]
+fn matches_float_pooling() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let pools = [
+        Pool1d::new(Pooling::Max, 3, 8, 2),
+        Pool1d {
+            stride: 1,
+            ..Pool1d::new(Pooling::Max, 2, 7, 3)
+        },
+        Pool1d {
+            stride: 2,
+            ..Pool1d::new(Pooling::Average, 2, 9, 3)
+        },
+        Pool1d::new(Pooling::Average, 3, 8, 4),
+        Pool1d::global_average(2, 6),
+    ];
+
+    for pool in pools {
+        for zero_point in [-128, -37, 0, 5, 127] {
+            let layer = quantized(pool, zero_point);
+            for _ in 0..50 {
+                let input = Array1::from_shape_fn(pool.input_size(), |_| rng.gen::<i8>());
+                assert_eq!(
+                    layer.feedforward(&input),
+                    reference(&layer, &input),
+                    "{pool:?}, zero point {zero_point}, input {input}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+
+fn asymmetric() -> QuantizationConfig {
+    QuantizationConfig {
+        activation_scheme: QuantizationScheme::Asymmetric,
+        ..QuantizationConfig::default()
+    }
+}
+
+/// Features in [0, 1], like the normalized sensor inputs.
+fn sensor_data(rng: &mut StdRng, inputs: usize) -> Vec<DataPoint> {
+    (0..256)
+        .map(|_| DataPoint {
+            inputs: Array1::from_shape_fn(inputs, |_| rng.gen_range(0.0..1.0)),
+            targets: Array1::zeros(1),
+        })
+        .collect()
+}
+
+/// Largest difference between the float outputs and the dequantized integer outputs.
+fn max_error(
+    network: &NeuralNetwork,
+    quantized: &QuantizedNeuralNetwork,
+    data: &[DataPoint],
+) -> f32 {
+    data.iter()
+        .flat_map(|point| {
+            let expected = network.feedforward(&point.inputs);
+            let actual = quantized.dequantize_output(
+                &quantized.feedforward(&quantized.quantize_input(&point.inputs)),
+            );
+            (&expected - &actual).mapv(f32::abs).to_vec()
+        })
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn non_negative_range_uses_all_levels() {
+    let range = ActivationRange { min: 0.0, max: 1.0 };
+    let params = range.quantization_params(QuantizationScheme::Asymmetric);
+    assert_eq!(params.zero_point, -128);
+    assert_eq!(params.scale, 1.0 / 255.0);
+    assert_eq!(params.quantize(0.0), -128);
+    assert_eq!(params.quantize(1.0), 127);
+
+    let symmetric = range.quantization_params(QuantizationScheme::Symmetric);
+    assert_eq!(symmetric.quantize(0.0), 0);
+    assert_eq!(symmetric.quantize(1.0), 127);
+}
+
+#[test]
+fn zero_stays_representable() {
+    for (min, max) in [(-0.3, 1.7), (0.2, 0.9), (-2.0, -0.5), (-1.0, 1.0)] {
+        let params =
+            ActivationRange { min, max }.quantization_params(QuantizationScheme::Asymmetric);
+        assert_eq!(params.dequantize(params.quantize(0.0)), 0.0, "{min} {max}");
+    }
+}
+
+/// With the zero-point correction terms the integer matmuls track the float network, and
+/// more closely than the symmetric scheme on the non-negative input and ReLU outputs.
+#[test]
+fn asymmetric_network_matches_float() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let network = NeuralNetwork::new(
+        &[8, 16, 8, 4],
+        &[
+            ActivationFunction::ReLU,
+            ActivationFunction::ReLU,
+            ActivationFunction::Linear,
+        ],
+        &mut rng,
+    );
+    let data = sensor_data(&mut rng, 8);
+    let calibration = network.calibrate(&data, CalibrationMethod::MinMax);
+
+    let symmetric = network
+        .quantize_with(&calibration, &QuantizationConfig::default())
+        .unwrap();
+    let asymmetric = network.quantize_with(&calibration, &asymmetric()).unwrap();
+
+    let symmetric_error = max_error(&network, &symmetric, &data);
+    let asymmetric_error = max_error(&network, &asymmetric, &data);
+    assert!(asymmetric_error < 0.05, "{asymmetric_error}");
+    assert!(
+        asymmetric_error < symmetric_error,
+        "{asymmetric_error} >= {symmetric_error}"
+    );
+}
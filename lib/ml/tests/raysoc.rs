@@ -0,0 +1,168 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+use serde::Deserialize;
+
+/// The fields of the RaySoC network file the MLP in MlExec.scala is built from.
+#[derive(Deserialize)]
+struct RaySocNetwork {
+    layers: Vec<usize>,
+    weights: Vec<Vec<Vec<i32>>>,
+    biases: Vec<Vec<i32>>,
+    multipliers: Vec<Vec<i32>>,
+    shifts: Vec<Vec<i32>>,
+    sigmoid_tables: Vec<Vec<i32>>,
+    sigmoid_interpolate: bool,
+}
+
+/// The `Requantizer` component: 64 bit product, rounding bit, arithmetic shift, saturation.
+fn requantizer(x: i32, multiplier: i32, shift: i32) -> i32 {
+    let total_shift = shift + 31;
+    let rounded = (x as i64 * multiplier as i64 + (1i64 << (total_shift - 1))) >> total_shift;
+    rounded.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// The `Sigmoid` component.
+fn sigmoid_unit(table: &[i32], interpolate: bool, x: i32) -> i32 {
+    let entries = table.len() as i32;
+    let half = entries / 2;
+    let interval = x >> 7;
+    if interval < -half {
+        return table[0];
+    }
+    if interval > half || (interpolate && interval == half) {
+        return table[entries as usize - 1];
+    }
+
+    let index = (interval + half) as usize;
+    if !interpolate {
+        return table[index];
+    }
+    let (low, high) = (table[index], table[index + 1]);
+    low + (((high - low) * (x & 0x7f) + 64) >> 7)
+}
+
+/// One inference of the `MLP` component: every layer loads its biases into the MAC array,
+/// accumulates one input per cycle and passes each lane through its requantizer and the
+/// sigmoid unit.
+fn run_mlp(network: &RaySocNetwork, input: &Array1<i8>) -> Vec<i8> {
+    let mut z: Vec<i32> = input.iter().map(|&x| x as i32).collect();
+    for l in 1..network.layers.len() {
+        z = (0..network.layers[l])
+            .map(|j| {
+                let acc = (0..network.layers[l - 1]).fold(network.biases[l - 1][j], |acc, k| {
+                    acc.wrapping_add(network.weights[l - 1][j][k] * z[k])
+                });
+                let x = requantizer(acc, network.multipliers[l - 1][j], network.shifts[l - 1][j]);
+                sigmoid_unit(
+                    &network.sigmoid_tables[l - 1],
+                    network.sigmoid_interpolate,
+                    x,
+                )
+            })
+            .collect();
+    }
+    z.into_iter().map(|x| x as i8).collect()
+}
+
+/// Exports `quantized` and checks the RTL datapath reproduces `feedforward` bit for bit.
+fn assert_matches_feedforward(name: &str, quantized: &QuantizedNeuralNetwork, rng: &mut StdRng) {
+    let path = std::env::temp_dir().join(format!("ray-ml-raysoc-{name}.json"));
+    quantized.export_raysoc_network(&path).unwrap();
+    let network: RaySocNetwork =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    for _ in 0..64 {
+        let input = quantized.quantize_input(&Array1::from_shape_fn(network.layers[0], |_| {
+            rng.gen_range(-1.0..1.0)
+        }));
+        assert_eq!(
+            run_mlp(&network, &input),
+            quantized.feedforward(&input).to_vec()
+        );
+    }
+}
+
+/// Pre-activations on the LUT input grid: saturation on both sides, both ends of the table,
+/// negative fractions and the last interpolated interval.
+const SIGMOID_INPUTS: [i32; 18] = [
+    -5000, -1025, -1024, -1000, -300, -129, -128, -1, 0, 1, 64, 100, 200, 640, 1000, 1023, 1024,
+    5000,
+];
+
+/// The interpolated 17 entry table for symmetric and asymmetric outputs and its outputs for
+/// [`SIGMOID_INPUTS`]. SigmoidSim.scala drives the `Sigmoid` component with the same vectors.
+const SIGMOID_VECTORS: [(QuantizationParams, [i8; 17], [i8; 18]); 2] = [
+    (
+        QuantizationParams {
+            scale: 1.0 / 127.0,
+            zero_point: 0,
+        },
+        [
+            0, 0, 0, 1, 2, 6, 15, 34, 64, 93, 112, 121, 125, 126, 127, 127, 127,
+        ],
+        [
+            0, 0, 0, 0, 12, 34, 34, 64, 64, 64, 79, 87, 104, 126, 127, 127, 127, 127,
+        ],
+    ),
+    (
+        QuantizationParams {
+            scale: 1.0 / 255.0,
+            zero_point: -128,
+        },
+        [
+            -128, -128, -127, -126, -123, -116, -98, -59, -1, 58, 97, 115, 122, 125, 126, 127, 127,
+        ],
+        [
+            -128, -128, -128, -128, -104, -59, -59, -1, -1, -1, 29, 45, 80, 125, 127, 127, 127, 127,
+        ],
+    ),
+];
+
+/// Pins the interpolated lookup, so a change on either side of the RTL boundary shows up
+/// here or in the simulation.
+#[test]
+fn interpolated_sigmoid_vectors() {
+    let lut = SigmoidLut {
+        interpolate: true,
+        ..SigmoidLut::default()
+    };
+    for (params, table, expected) in SIGMOID_VECTORS {
+        assert_eq!(lut.table(params), table);
+        let wide: Vec<i32> = table.iter().map(|&entry| entry as i32).collect();
+        for (x, expected) in SIGMOID_INPUTS.into_iter().zip(expected) {
+            assert_eq!(lut.lookup(&table, x), expected, "lookup({x})");
+            assert_eq!(
+                sigmoid_unit(&wide, true, x),
+                expected as i32,
+                "sigmoid_unit({x})"
+            );
+        }
+    }
+}
+
+fn sigmoid_network(rng: &mut StdRng) -> NeuralNetwork {
+    NeuralNetwork::new(&[6, 8, 5, 3], &[ActivationFunction::Sigmoid; 3], rng)
+}
+
+#[test]
+fn requantization() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let network = sigmoid_network(&mut rng);
+    assert_matches_feedforward("requantization", &network.quantize().unwrap(), &mut rng);
+}
+
+#[test]
+fn per_channel_requantization() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let network = sigmoid_network(&mut rng);
+    let config = QuantizationConfig {
+        weight_granularity: WeightGranularity::PerChannel,
+        ..QuantizationConfig::default()
+    };
+    let calibration = Calibration::uniform(network.layers.len(), ActivationRange::default());
+    let quantized = network.quantize_with(&calibration, &config).unwrap();
+    assert_matches_feedforward("per-channel", &quantized, &mut rng);
+}
@@ -0,0 +1,110 @@
+use ray_ml::*;
+
+const BASE: f32 = 0.1;
+
+fn assert_rates(schedule: &dyn LearningRateSchedule, expected: &[(usize, f32)]) {
+    for &(epoch, rate) in expected {
+        let actual = schedule.learning_rate(BASE, epoch, 0);
+        assert!(
+            (actual - rate).abs() < 1e-7,
+            "epoch {epoch}: {actual} != {rate}"
+        );
+    }
+}
+
+#[test]
+fn step_decay() {
+    let schedule = StepDecay {
+        step_size: 10,
+        gamma: 0.5,
+    };
+    assert_rates(&schedule, &[(0, 0.1), (9, 0.1), (10, 0.05), (25, 0.025)]);
+}
+
+#[test]
+fn exponential() {
+    let schedule = Exponential { gamma: 0.9 };
+    assert_rates(&schedule, &[(0, 0.1), (1, 0.09), (3, 0.0729)]);
+}
+
+#[test]
+fn cosine_annealing() {
+    let schedule = CosineAnnealing::new(4, 0.02);
+    assert_rates(&schedule, &[(0, 0.1), (2, 0.06), (4, 0.1), (6, 0.06)]);
+}
+
+/// SGDR restarts at epochs 4, 4 + 8 and 4 + 8 + 16 when every period doubles.
+#[test]
+fn cosine_annealing_restarts() {
+    let schedule = CosineAnnealing {
+        period_mult: 2,
+        ..CosineAnnealing::new(4, 0.0)
+    };
+    assert_rates(
+        &schedule,
+        &[
+            (0, 0.1),
+            (2, 0.05),
+            (4, 0.1),
+            (8, 0.05),
+            (12, 0.1),
+            (20, 0.05),
+            (28, 0.1),
+        ],
+    );
+}
+
+#[test]
+fn linear_warmup() {
+    let schedule = LinearWarmup::new(
+        4,
+        StepDecay {
+            step_size: 1,
+            gamma: 0.5,
+        },
+    );
+    let rates: Vec<f32> = (0..6)
+        .map(|step| schedule.learning_rate(BASE, 0, step))
+        .collect();
+    assert_eq!(rates, [0.025, 0.05, 0.075, 0.1, 0.1, 0.1]);
+    assert_eq!(schedule.learning_rate(BASE, 1, 10), 0.05);
+}
+
+/// The rate drops once the loss has failed to improve for more than `patience` epochs, and
+/// the count starts over after every drop or improvement.
+#[test]
+fn reduce_on_plateau() {
+    let mut schedule = ReduceOnPlateau::new(0.5, 2);
+    let losses = [1.0, 0.9, 0.9, 0.95, 0.9, 0.8, 0.8, 0.8, 0.8, 0.8, 0.8, 0.8];
+    let rates: Vec<f32> = losses
+        .iter()
+        .map(|&loss| {
+            schedule.end_epoch(loss);
+            schedule.learning_rate(BASE, 0, 0)
+        })
+        .collect();
+    assert_eq!(
+        rates,
+        [0.1, 0.1, 0.1, 0.1, 0.05, 0.05, 0.05, 0.05, 0.025, 0.025, 0.025, 0.0125]
+    );
+}
+
+#[test]
+fn reduce_on_plateau_state() {
+    let mut schedule = ReduceOnPlateau::new(0.5, 1);
+    for loss in [1.0, 1.0, 1.0, 1.0] {
+        schedule.end_epoch(loss);
+    }
+
+    let mut restored = ReduceOnPlateau::new(0.5, 1);
+    restored.load_state(&schedule.state()).unwrap();
+    for loss in [1.0, 0.5, 0.5, 0.5] {
+        schedule.end_epoch(loss);
+        restored.end_epoch(loss);
+        assert_eq!(
+            restored.learning_rate(BASE, 0, 0),
+            schedule.learning_rate(BASE, 0, 0)
+        );
+    }
+    assert!(restored.load_state(&[1.0]).is_err());
+}
@@ -0,0 +1,54 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::Array1;
+use ray_ml::*;
+
+fn random_data(rng: &mut StdRng) -> Vec<DataPoint> {
+    (0..50)
+        .map(|_| {
+            let inputs = Array1::from_shape_fn(3, |_| rng.gen_range(-1.0..1.0));
+            let targets = Array1::from_elem(1, f32::from(inputs.sum() > 0.0));
+            DataPoint { inputs, targets }
+        })
+        .collect()
+}
+
+/// Trains a fresh copy of the same network for the given `train` calls.
+fn train(seed: u64, batch_size: Option<usize>, calls: &[usize]) -> Vec<f32> {
+    let mut rng = StdRng::seed_from_u64(1);
+    let data = random_data(&mut rng);
+    let mut network = NeuralNetwork::new(
+        &[3, 4, 1],
+        &[ActivationFunction::Tanh, ActivationFunction::Sigmoid],
+        &mut rng,
+    );
+
+    let mut trainer = Trainer::new(&mut network, 0.1, 0.9);
+    trainer.seed = seed;
+    trainer.batch_size = batch_size;
+    trainer.callbacks.clear();
+    for &epochs in calls {
+        trainer.train(&data, epochs).unwrap();
+    }
+    network.parameters().iter().flatten().copied().collect()
+}
+
+#[test]
+fn same_seed_same_order() {
+    assert_eq!(train(3, Some(8), &[4]), train(3, Some(8), &[4]));
+    assert_ne!(train(3, Some(8), &[4]), train(4, Some(8), &[4]));
+}
+
+/// The order depends on the epoch counter, not on how the epochs are split across calls.
+#[test]
+fn order_follows_the_epoch() {
+    assert_eq!(train(3, Some(8), &[4]), train(3, Some(8), &[1, 3]));
+    assert_eq!(train(3, Some(8), &[4]), train(3, Some(8), &[2, 1, 1]));
+}
+
+/// Full-batch training has nothing to shuffle, so the seed does not matter.
+#[test]
+fn full_batch_ignores_seed() {
+    assert_eq!(train(3, None, &[4]), train(4, None, &[4]));
+    assert_eq!(train(3, Some(50), &[4]), train(4, None, &[4]));
+}
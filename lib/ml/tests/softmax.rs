@@ -0,0 +1,74 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_ml::nd::{array, Array1};
+use ray_ml::*;
+
+fn assert_close(actual: &Array1<f32>, expected: &Array1<f32>) {
+    assert!(
+        actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (a - e).abs() < 1e-6),
+        "{actual} != {expected}"
+    );
+}
+
+#[test]
+fn softmax_sums_to_one() {
+    let a = ActivationFunction::Softmax.activate(&array![1.0, 2.0, 3.0]);
+    let e = [1.0f32.exp(), 2.0f32.exp(), 3.0f32.exp()];
+    let sum: f32 = e.iter().sum();
+    assert_close(&a, &array![e[0] / sum, e[1] / sum, e[2] / sum]);
+
+    // Large logits must not overflow.
+    let a = ActivationFunction::Softmax.activate(&array![1000.0, 1000.0]);
+    assert_close(&a, &array![0.5, 0.5]);
+}
+
+/// The fused delta is the cross-entropy gradient taken through the full softmax Jacobian.
+#[test]
+fn fused_delta_matches_chain_rule() {
+    let loss = CategoricalCrossEntropy::default();
+    let z = array![0.3, -1.2, 2.0, 0.1];
+    let a = ActivationFunction::Softmax.activate(&z);
+    let target = array![0.0, 0.0, 1.0, 0.0];
+
+    let fused = loss.output_delta(&z, &a, &target, ActivationFunction::Softmax);
+    assert_close(&fused, &(&a - &target));
+
+    let chained = ActivationFunction::Softmax.backward(&z, &loss.gradient(&a, &target));
+    assert_close(&fused, &chained);
+}
+
+/// The integer network picks the same class as the float one for nearly every input.
+#[test]
+fn quantized_prediction_matches_float() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let network = NeuralNetwork::new(
+        &[6, 16, 5],
+        &[ActivationFunction::ReLU, ActivationFunction::Softmax],
+        &mut rng,
+    );
+    let data: Vec<DataPoint> = (0..500)
+        .map(|_| DataPoint {
+            inputs: Array1::from_shape_fn(6, |_| rng.gen_range(-1.0..1.0)),
+            targets: Array1::zeros(5),
+        })
+        .collect();
+    let quantized = network
+        .quantize_calibrated(&network.calibrate(&data, CalibrationMethod::MinMax))
+        .unwrap();
+
+    let samples = data.len();
+    let agreeing = data
+        .iter()
+        .filter(|point| {
+            let output = network.feedforward(&point.inputs);
+            let expected = (0..5)
+                .max_by(|&i, &j| output[i].total_cmp(&output[j]))
+                .unwrap();
+            quantized.predict(&quantized.quantize_input(&point.inputs)) == expected
+        })
+        .count();
+    assert!(agreeing * 100 >= samples * 95, "{agreeing} of {samples}");
+}
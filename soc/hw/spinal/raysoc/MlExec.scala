@@ -13,42 +13,66 @@ case object Params {
   val MAX_OUTPUT_SIZE = 32
 }
 
-case class Sigmoid() extends Component {
+// Requantizes an accumulator like Requantization::apply in the ml crate:
+// (x * multiplier + 2^(30 + shift)) >> (31 + shift), saturated to 32 bits.
+case class Requantizer() extends Component {
   val io = new Bundle {
     val x = in SInt(32 bits)
-    val y = out SInt(8 bits)
+    val multiplier = in SInt(32 bits)
+    val shift = in SInt(8 bits)
+    val y = out SInt(32 bits)
   }
 
-  val x_shifted = io.x >> 7
+  val totalShift = (io.shift + 31).asUInt.resize(6 bits)
+  val product = io.x * io.multiplier
+  val rounding = S(1, 64 bits) |<< (totalShift - 1)
+  val rounded = (product + rounding) >> totalShift
 
-  val x_clamped = SInt(5 bits)
-  when(x_shifted > 8) {
-    x_clamped := 8
-  } elsewhen(x_shifted < -8) {
-    x_clamped := -8
+  when(rounded > S(Int.MaxValue, 64 bits)) {
+    io.y := S(Int.MaxValue, 32 bits)
+  } elsewhen(rounded < S(Int.MinValue, 64 bits)) {
+    io.y := S(Int.MinValue, 32 bits)
   } otherwise {
-    x_clamped := x_shifted.resize(5 bits)
+    io.y := rounded.resize(32 bits)
   }
+}
 
-  val index = SInt(5 bits)
-  index := (x_clamped + 8).resize(5 bits)
-
-  val sigmoid_output = SInt(8 bits)
-  switch(index) {
-    is(S(0))  { sigmoid_output := S(0, 8 bits) }
-    is(S(1))  { sigmoid_output := S(4, 8 bits) }
-    is(S(2))  { sigmoid_output := S(8, 8 bits) }
-    is(S(3))  { sigmoid_output := S(15, 8 bits) }
-    is(S(4))  { sigmoid_output := S(26, 8 bits) }
-    is(S(5))  { sigmoid_output := S(41, 8 bits) }
-    is(S(6))  { sigmoid_output := S(60, 8 bits) }
-    is(S(7))  { sigmoid_output := S(81, 8 bits) }
-    is(S(8))  { sigmoid_output := S(103, 8 bits) }
-    is(S(9))  { sigmoid_output := S(122, 8 bits) }
-    default   { sigmoid_output := S(127, 8 bits) }
+// Looks a requantized pre-activation up in a sigmoid table exported by the ml crate,
+// like SigmoidLut::lookup: x >> 7 selects the entry, saturating past both ends.
+case class Sigmoid(entries: Int, interpolate: Boolean) extends Component {
+  val io = new Bundle {
+    val x = in SInt(32 bits)
+    val table = in Vec(SInt(8 bits), entries)
+    val y = out SInt(8 bits)
   }
 
-  io.y := sigmoid_output
+  val half = entries / 2
+  val indexWidth = log2Up(entries)
+  val interval = io.x >> 7
+  val index = (interval + half).asUInt.resize(indexWidth bits)
+
+  if (!interpolate) {
+    when(interval > half) {
+      io.y := io.table(entries - 1)
+    } elsewhen(interval < -half) {
+      io.y := io.table(0)
+    } otherwise {
+      io.y := io.table(index)
+    }
+  } else {
+    val fraction = io.x(6 downto 0).asUInt.intoSInt
+    val low = io.table(index).resize(10 bits)
+    val high = io.table(index + 1).resize(10 bits)
+    val step = ((high - low) * fraction + 64) >> 7
+
+    when(interval >= half) {
+      io.y := io.table(entries - 1)
+    } elsewhen(interval < -half) {
+      io.y := io.table(0)
+    } otherwise {
+      io.y := (low + step.resize(10 bits)).resize(8 bits)
+    }
+  }
 }
 
 
@@ -97,10 +121,21 @@ case class MacArray(count: Int) extends Component {
   }
 }
 
-case class MLP(layers: List[Int], weights: List[Array[Array[Int]]], biases: List[Array[Int]]) extends Component {
+case class MLP(
+  layers: List[Int],
+  weights: List[Array[Array[Int]]],
+  biases: List[Array[Int]],
+  multipliers: List[Array[Int]],
+  shifts: List[Array[Int]],
+  sigmoidTables: List[Array[Int]],
+  sigmoidInterpolate: Boolean
+) extends Component {
   assert(layers.length > 2)
   assert(layers.length == weights.length + 1)
   assert(layers.length == biases.length + 1)
+  assert(layers.length == multipliers.length + 1)
+  assert(layers.length == shifts.length + 1)
+  assert(layers.length == sigmoidTables.length + 1)
 
   val maxLayerSize = layers.max
   val inputLayerSize = layers.head
@@ -132,9 +167,33 @@ case class MLP(layers: List[Int], weights: List[Array[Array[Int]]], biases: List
   val z = Vec(Reg(SInt(8 bits)) init(0), maxLayerSize)
   val activations = Vec(Reg(SInt(8 bits)) init(0), maxLayerSize)
 
-  val sigmoids = for (i <- 0 until maxLayerSize) yield new Sigmoid
+  val state = Reg(UInt(16 bits)) init(0)
+  val layer = Reg(UInt(16 bits)) init(0)
+
+  val sigmoidEntries = sigmoidTables.head.length
+  val requantizers = for (i <- 0 until maxLayerSize) yield new Requantizer
+  val sigmoids = for (i <- 0 until maxLayerSize) yield new Sigmoid(sigmoidEntries, sigmoidInterpolate)
   for (i <- 0 until maxLayerSize) {
-    sigmoids(i).io.x := macArray.io.p(i)
+    requantizers(i).io.x := macArray.io.p(i)
+    requantizers(i).io.multiplier := 0
+    requantizers(i).io.shift := 0
+    for (k <- 0 until sigmoidEntries) {
+      sigmoids(i).io.table(k) := 0
+    }
+
+    // Every layer has its own table and every neuron its own requantization, so per-channel
+    // weight scales work too. Both are picked while the layer is computed.
+    for (l <- 1 until layers.length if i < layers(l)) {
+      when(layer === l) {
+        requantizers(i).io.multiplier := multipliers(l - 1)(i)
+        requantizers(i).io.shift := shifts(l - 1)(i)
+        for (k <- 0 until sigmoidEntries) {
+          sigmoids(i).io.table(k) := sigmoidTables(l - 1)(k)
+        }
+      }
+    }
+
+    sigmoids(i).io.x := requantizers(i).io.y
     activations(i) := sigmoids(i).io.y
   }
   
@@ -147,9 +206,6 @@ case class MLP(layers: List[Int], weights: List[Array[Array[Int]]], biases: List
     macArray.io.c(i) := c(i)
   }
 
-  val state = Reg(UInt(16 bits)) init(0)
-  val layer = Reg(UInt(16 bits)) init(0)
-
   when(io.en === False) {
     state := 0
     layer := 0
@@ -228,12 +284,13 @@ case class MlExec() extends Component {
 
   val networkData = RSNReader.readNetworkData(networkPath)
   val (layers, weights, biases) = RSNReader.convertNetworkData(networkData)
+  val (multipliers, shifts, sigmoidTables) = RSNReader.convertActivationData(networkData)
 
   val io = new Bundle {
     val apb  = slave(Apb3(Apb3Config(addressWidth = 8, dataWidth = 32)))
   }
 
-  val mlp = MLP(layers, weights, biases)
+  val mlp = MLP(layers, weights, biases, multipliers, shifts, sigmoidTables, networkData.sigmoid_interpolate)
 
   val ctrl = Apb3SlaveFactory(io.apb)
   val enable = ctrl.createReadAndWrite(Bool(), 0)
@@ -0,0 +1,40 @@
+package raysoc
+
+import spinal.core._
+import spinal.core.sim._
+
+// Drives the interpolating Sigmoid with the vectors pinned in lib/ml/tests/raysoc.rs
+// (SIGMOID_INPUTS and SIGMOID_VECTORS), which SigmoidLut::lookup produces for the same
+// tables. Both sides have to agree bit for bit, so a change to either breaks one of them.
+object SigmoidSim extends App {
+  val inputs = Seq(
+    -5000, -1025, -1024, -1000, -300, -129, -128, -1, 0, 1, 64, 100, 200, 640, 1000, 1023, 1024,
+    5000
+  )
+
+  // (table, expected outputs) for symmetric and asymmetric outputs.
+  val vectors = Seq(
+    (
+      Seq(0, 0, 0, 1, 2, 6, 15, 34, 64, 93, 112, 121, 125, 126, 127, 127, 127),
+      Seq(0, 0, 0, 0, 12, 34, 34, 64, 64, 64, 79, 87, 104, 126, 127, 127, 127, 127)
+    ),
+    (
+      Seq(-128, -128, -127, -126, -123, -116, -98, -59, -1, 58, 97, 115, 122, 125, 126, 127, 127),
+      Seq(-128, -128, -128, -128, -104, -59, -59, -1, -1, -1, 29, 45, 80, 125, 127, 127, 127, 127)
+    )
+  )
+
+  Config.sim.compile(Sigmoid(17, interpolate = true)).doSim { dut =>
+    for ((table, expected) <- vectors) {
+      for ((entry, k) <- table.zipWithIndex) {
+        dut.io.table(k) #= entry
+      }
+      for ((x, y) <- inputs.zip(expected)) {
+        dut.io.x #= x
+        sleep(1)
+        assert(dut.io.y.toInt == y, s"sigmoid($x) = ${dut.io.y.toInt}, expected $y")
+      }
+    }
+    println("Sigmoid matches SigmoidLut::lookup")
+  }
+}
@@ -6,7 +6,11 @@ import upickle.default._
 case class RSNData(
   layers: List[Int],
   weights: List[List[List[Int]]],
-  biases: List[List[Int]]
+  biases: List[List[Int]],
+  multipliers: List[List[Int]],
+  shifts: List[List[Int]],
+  sigmoid_tables: List[List[Int]],
+  sigmoid_interpolate: Boolean
 )
 
 object RSNData {
@@ -28,4 +32,19 @@ object RSNReader {
     val biases = nd.biases.map(_.toArray)
     (layers, weights, biases)
   }
+
+  def convertActivationData(nd: RSNData): (List[Array[Int]], List[Array[Int]], List[Array[Int]]) = {
+    // Every neuron needs the requantization of its accumulator, as exported by the ml crate.
+    require(nd.multipliers.length == nd.layers.length - 1, "Every layer needs multipliers")
+    require(nd.shifts.length == nd.layers.length - 1, "Every layer needs shifts")
+    for ((neurons, l) <- nd.layers.tail.zipWithIndex) {
+      require(nd.multipliers(l).length == neurons, s"Layer ${l + 1} needs $neurons multipliers")
+      require(nd.shifts(l).length == neurons, s"Layer ${l + 1} needs $neurons shifts")
+      require(nd.shifts(l).forall(shift => shift >= -30 && shift <= 31), s"Layer ${l + 1} has a shift outside [-30, 31]")
+    }
+    val multipliers = nd.multipliers.map(_.toArray)
+    val shifts = nd.shifts.map(_.toArray)
+    val sigmoidTables = nd.sigmoid_tables.map(_.toArray)
+    (multipliers, shifts, sigmoidTables)
+  }
 }